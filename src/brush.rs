@@ -0,0 +1,275 @@
+// How a brush combines with the samples it covers, each weighted by the brush's coverage
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum BlendMode {
+    // Lowers samples towards empty, digging the shape out
+    Carve,
+    // Raises samples towards solid, building the shape up
+    Fill,
+    // Replaces samples with the value
+    Set(f32),
+    // Adds the amount, clamped to 0..=1
+    Add(f32),
+    // Subtracts the amount, clamped to 0..=1
+    Subtract(f32),
+    // Multiplies samples by the factor
+    Multiply(f32),
+    // Like fill but rounds the join with existing terrain over the given width in density
+    SmoothUnion(f32),
+}
+
+impl BlendMode {
+    // New value of a sample the brush covers by coverage, from 0 (untouched) to 1 (fully covered)
+    pub fn blend(self, value: f32, coverage: f32) -> f32 {
+        match self {
+            BlendMode::Carve => value.min(1.0 - coverage),
+            BlendMode::Fill => value.max(coverage),
+            BlendMode::Set(target) => value + (target - value) * coverage,
+            BlendMode::Add(amount) => (value + amount * coverage).clamp(0.0, 1.0),
+            BlendMode::Subtract(amount) => (value - amount * coverage).clamp(0.0, 1.0),
+            BlendMode::Multiply(factor) => value * (1.0 + (factor - 1.0) * coverage),
+            // The width fades out with the coverage so the brush has no hard edge
+            BlendMode::SmoothUnion(width) => smooth_max(value, coverage, width * coverage),
+        }
+    }
+}
+
+// Coverage of a sample at a signed distance from the brush's edge, negative inside,
+// ramping from 0 at the edge to 1 at falloff inside it
+pub fn coverage(distance: f32, falloff: f32) -> f32 {
+    if falloff <= 0.0 {
+        return if distance < 0.0 { 1.0 } else { 0.0 };
+    }
+
+    (-distance / falloff).clamp(0.0, 1.0)
+}
+
+// Samples of a size by size grid, with its first sample at origin, that lie inside the
+// brush, along with their distances. Only the samples within the bounds are measured
+pub(crate) fn covered_samples<'a, B: Brush + ?Sized>(
+    brush: &'a B,
+    origin: (f32, f32),
+    size: usize,
+) -> impl Iterator<Item = (usize, usize, f32)> + 'a {
+    let (min, max) = brush.bounds();
+    let last = size as f32 - 1.0;
+
+    let (min_x, max_x) = ((min.0 - origin.0).ceil().max(0.0), (max.0 - origin.0).floor().min(last));
+    let (min_y, max_y) = ((min.1 - origin.1).ceil().max(0.0), (max.1 - origin.1).floor().min(last));
+    let (columns, rows) = if min_x > max_x || min_y > max_y {
+        (0..0, 0..0)
+    } else {
+        (min_x as usize..max_x as usize + 1, min_y as usize..max_y as usize + 1)
+    };
+
+    rows.flat_map(move |y| columns.clone().map(move |x| (x, y)))
+        .filter_map(move |(x, y)| {
+            let distance = brush.distance(origin.0 + x as f32, origin.1 + y as f32);
+            (distance < 0.0).then_some((x, y, distance))
+        })
+}
+
+// Polynomial smooth maximum, equal to max(a, b) once they are width apart
+pub(crate) fn smooth_max(a: f32, b: f32, width: f32) -> f32 {
+    if width <= 0.0 {
+        return a.max(b);
+    }
+
+    let h = (0.5 + 0.5 * (b - a) / width).clamp(0.0, 1.0);
+    a + (b - a) * h + width * h * (1.0 - h)
+}
+
+// A shape that can be painted into samples
+pub trait Brush {
+    // Signed distance from (x, y) to the edge of the shape, negative inside
+    fn distance(&self, x: f32, y: f32) -> f32;
+
+    // Smallest and largest corners of a box holding every point inside the shape
+    fn bounds(&self) -> ((f32, f32), (f32, f32));
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Circle {
+    pub centre: (f32, f32),
+    pub radius: f32,
+}
+
+impl Brush for Circle {
+    fn distance(&self, x: f32, y: f32) -> f32 {
+        length(x - self.centre.0, y - self.centre.1) - self.radius
+    }
+
+    fn bounds(&self) -> ((f32, f32), (f32, f32)) {
+        square_bounds(self.centre, self.radius)
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Rectangle {
+    pub centre: (f32, f32),
+    // Half the width and height before rotating
+    pub half_size: (f32, f32),
+    // Anticlockwise rotation in radians
+    pub rotation: f32,
+}
+
+impl Rectangle {
+    pub fn new(centre: (f32, f32), size: (f32, f32)) -> Self {
+        Self {
+            centre,
+            half_size: (size.0 / 2.0, size.1 / 2.0),
+            rotation: 0.0,
+        }
+    }
+
+    pub fn rotated(mut self, rotation: f32) -> Self {
+        self.rotation = rotation;
+
+        self
+    }
+}
+
+impl Brush for Rectangle {
+    fn distance(&self, x: f32, y: f32) -> f32 {
+        // Rotate the point back into the rectangle's frame
+        let (sin, cos) = self.rotation.sin_cos();
+        let (dx, dy) = (x - self.centre.0, y - self.centre.1);
+        let (local_x, local_y) = (cos * dx + sin * dy, cos * dy - sin * dx);
+
+        let outside_x = local_x.abs() - self.half_size.0;
+        let outside_y = local_y.abs() - self.half_size.1;
+
+        length(outside_x.max(0.0), outside_y.max(0.0)) + outside_x.max(outside_y).min(0.0)
+    }
+
+    fn bounds(&self) -> ((f32, f32), (f32, f32)) {
+        let (sin, cos) = self.rotation.sin_cos();
+        let extent_x = cos.abs() * self.half_size.0 + sin.abs() * self.half_size.1;
+        let extent_y = sin.abs() * self.half_size.0 + cos.abs() * self.half_size.1;
+
+        (
+            (self.centre.0 - extent_x, self.centre.1 - extent_y),
+            (self.centre.0 + extent_x, self.centre.1 + extent_y),
+        )
+    }
+}
+
+// A line segment thickened by the radius, with rounded ends
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Capsule {
+    pub from: (f32, f32),
+    pub to: (f32, f32),
+    pub radius: f32,
+}
+
+impl Capsule {
+    pub fn line(from: (f32, f32), to: (f32, f32), width: f32) -> Self {
+        Self {
+            from,
+            to,
+            radius: width / 2.0,
+        }
+    }
+}
+
+impl Brush for Capsule {
+    fn distance(&self, x: f32, y: f32) -> f32 {
+        let (closest_x, closest_y) = closest_on_segment((x, y), self.from, self.to);
+        length(x - closest_x, y - closest_y) - self.radius
+    }
+
+    fn bounds(&self) -> ((f32, f32), (f32, f32)) {
+        (
+            (self.from.0.min(self.to.0) - self.radius, self.from.1.min(self.to.1) - self.radius),
+            (self.from.0.max(self.to.0) + self.radius, self.from.1.max(self.to.1) + self.radius),
+        )
+    }
+}
+
+// A closed polygon, convex or concave, in either winding order
+#[derive(Clone, Debug, PartialEq)]
+pub struct Polygon {
+    pub points: Vec<(f32, f32)>,
+}
+
+impl Polygon {
+    pub fn new(points: Vec<(f32, f32)>) -> Self {
+        assert!(points.len() >= 3, "a polygon needs at least 3 points");
+        Self {
+            points
+        }
+    }
+}
+
+impl Brush for Polygon {
+    fn distance(&self, x: f32, y: f32) -> f32 {
+        let mut distance_squared = f32::INFINITY;
+        let mut inside = false;
+
+        for (i, &from) in self.points.iter().enumerate() {
+            let to = self.points[(i + 1) % self.points.len()];
+
+            let (closest_x, closest_y) = closest_on_segment((x, y), from, to);
+            distance_squared = distance_squared.min((x - closest_x).powi(2) + (y - closest_y).powi(2));
+
+            // Even-odd rule: count the edges a ray towards +x crosses
+            if (from.1 > y) != (to.1 > y) {
+                let crossing_x = from.0 + (y - from.1) / (to.1 - from.1) * (to.0 - from.0);
+                if x < crossing_x {
+                    inside = !inside;
+                }
+            }
+        }
+
+        if inside { -distance_squared.sqrt() } else { distance_squared.sqrt() }
+    }
+
+    fn bounds(&self) -> ((f32, f32), (f32, f32)) {
+        self.points.iter().fold(
+            ((f32::INFINITY, f32::INFINITY), (f32::NEG_INFINITY, f32::NEG_INFINITY)),
+            |(min, max), point| (
+                (min.0.min(point.0), min.1.min(point.1)),
+                (max.0.max(point.0), max.1.max(point.1)),
+            ),
+        )
+    }
+}
+
+// The area between two circles sharing a centre
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Ring {
+    pub centre: (f32, f32),
+    pub inner_radius: f32,
+    pub outer_radius: f32,
+}
+
+impl Brush for Ring {
+    fn distance(&self, x: f32, y: f32) -> f32 {
+        let middle = (self.inner_radius + self.outer_radius) / 2.0;
+        let half_thickness = (self.outer_radius - self.inner_radius) / 2.0;
+
+        (length(x - self.centre.0, y - self.centre.1) - middle).abs() - half_thickness
+    }
+
+    fn bounds(&self) -> ((f32, f32), (f32, f32)) {
+        square_bounds(self.centre, self.outer_radius)
+    }
+}
+
+fn length(x: f32, y: f32) -> f32 {
+    (x * x + y * y).sqrt()
+}
+
+fn square_bounds(centre: (f32, f32), radius: f32) -> ((f32, f32), (f32, f32)) {
+    ((centre.0 - radius, centre.1 - radius), (centre.0 + radius, centre.1 + radius))
+}
+
+pub(crate) fn closest_on_segment(point: (f32, f32), from: (f32, f32), to: (f32, f32)) -> (f32, f32) {
+    let (dx, dy) = (to.0 - from.0, to.1 - from.1);
+    let length_squared = dx * dx + dy * dy;
+    if length_squared == 0.0 {
+        return from;
+    }
+
+    let t = (((point.0 - from.0) * dx + (point.1 - from.1) * dy) / length_squared).clamp(0.0, 1.0);
+    (from.0 + dx * t, from.1 + dy * t)
+}
@@ -0,0 +1,65 @@
+#[repr(C)]
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Camera {
+    // World position at the centre of the view
+    pub position: (f32, f32),
+    // Clip space units per world unit, so 1.0 / zoom world units fit above the centre
+    pub zoom: f32,
+    // Anticlockwise rotation of the camera in radians
+    pub rotation: f32,
+    // Width over height of the view
+    pub aspect: f32,
+}
+
+impl Camera {
+    pub fn new(position: (f32, f32), zoom: f32) -> Self {
+        Self {
+            position,
+            zoom,
+            rotation: 0.0,
+            aspect: 1.0,
+        }
+    }
+
+    fn scale(&self) -> (f32, f32) {
+        (self.zoom / self.aspect, self.zoom)
+    }
+
+    // Column major matrix taking world positions to clip space
+    pub fn view_proj(&self) -> [[f32; 4]; 4] {
+        let (scale_x, scale_y) = self.scale();
+        let (sin, cos) = self.rotation.sin_cos();
+
+        let x_axis = [scale_x * cos, -scale_y * sin];
+        let y_axis = [scale_x * sin, scale_y * cos];
+        let translation = [
+            -(x_axis[0] * self.position.0 + y_axis[0] * self.position.1),
+            -(x_axis[1] * self.position.0 + y_axis[1] * self.position.1),
+        ];
+
+        [
+            [x_axis[0], x_axis[1], 0.0, 0.0],
+            [y_axis[0], y_axis[1], 0.0, 0.0],
+            [0.0, 0.0, 1.0, 0.0],
+            [translation[0], translation[1], 0.0, 1.0],
+        ]
+    }
+
+    // World position under a point in clip space, the inverse of view_proj
+    pub fn clip_to_world(&self, clip: (f32, f32)) -> (f32, f32) {
+        let (scale_x, scale_y) = self.scale();
+        let (sin, cos) = self.rotation.sin_cos();
+
+        let (u, v) = (clip.0 / scale_x, clip.1 / scale_y);
+
+        (
+            self.position.0 + cos * u - sin * v,
+            self.position.1 + sin * u + cos * v,
+        )
+    }
+
+    // Distance from the centre to the furthest visible world position
+    pub fn view_radius(&self) -> f32 {
+        (self.aspect * self.aspect + 1.0).sqrt() / self.zoom
+    }
+}
@@ -1,58 +1,171 @@
-use std::iter;
- 
+use crate::brush::{ self, BlendMode, Brush, Circle };
+use crate::contour::{ Contour, ContourBuilder };
+use crate::image_io::{ self, ExportOptions, ImageOptions, ImageSampler };
+use crate::mesh::MeshConfig;
+use crate::square_march::SquareSet;
+
+// Samples are stored row by row, so sample (x, y) is at data[y * size + x]
 #[repr(C)]
 #[derive(Clone)]
 pub struct Chunk {
-    pub data: Vec<Vec<f32>>,
-    pub size: usize
+    data: Vec<f32>,
+    size: usize
 }
 
 impl Chunk {
     pub fn new(size: usize) -> Self {
-        let data: Vec<Vec<f32>> = iter::repeat(iter::repeat(1.0).take(size).collect()).take(size).collect();
+        Self::filled(size, 1.0)
+    }
+
+    pub fn filled(size: usize, value: f32) -> Self {
+        assert!(size > 0, "chunk size must be at least one sample");
+        Self {
+            data: vec![value; size * size],
+            size
+        }
+    }
+
+    pub fn from_vec(size: usize, data: Vec<f32>) -> Self {
+        assert!(size > 0, "chunk size must be at least one sample");
+        assert_eq!(data.len(), size * size, "chunk data must hold size * size samples");
         Self {
             data,
             size
         }
     }
 
-    pub fn get_edge(&self, dir: i32) -> Vec<Vec<f32>> {
-        match dir {
-            0 => vec![self.data[0].clone()],
-            1 => self.data.iter().map(|x| vec![x[0]]).collect(),
-            2 => vec![vec![self.data[0][0]]],
-            _ => vec![],
+    // Samples the image as placed by the options, sample (0, 0) being the chunk's first
+    pub fn from_image(image: &image::DynamicImage, size: usize, options: ImageOptions) -> Self {
+        let mut chunk = Self::new(size);
+        ImageSampler::new(image, options).fill((0.0, 0.0), &mut chunk);
+
+        chunk
+    }
+
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    fn index(&self, x: usize, y: usize) -> usize {
+        debug_assert!(x < self.size && y < self.size, "sample ({x}, {y}) outside chunk of size {}", self.size);
+        y * self.size + x
+    }
+
+    pub fn get(&self, x: usize, y: usize) -> f32 {
+        self.data[self.index(x, y)]
+    }
+
+    pub fn set(&mut self, x: usize, y: usize, value: f32) -> &mut Self {
+        let index = self.index(x, y);
+        self.data[index] = value;
+
+        self
+    }
+
+    pub fn row(&self, y: usize) -> &[f32] {
+        &self.data[y * self.size..(y + 1) * self.size]
+    }
+
+    pub fn row_mut(&mut self, y: usize) -> &mut [f32] {
+        &mut self.data[y * self.size..(y + 1) * self.size]
+    }
+
+    pub fn column(&self, x: usize) -> impl Iterator<Item = f32> + '_ {
+        self.data.iter().skip(x).step_by(self.size).copied()
+    }
+
+    pub fn rows(&self) -> impl Iterator<Item = &[f32]> {
+        self.data.chunks_exact(self.size)
+    }
+
+    pub fn as_slice(&self) -> &[f32] {
+        &self.data
+    }
+
+    pub fn as_mut_slice(&mut self) -> &mut [f32] {
+        &mut self.data
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        bytemuck::cast_slice(&self.data)
+    }
+
+    pub fn into_vec(self) -> Vec<f32> {
+        self.data
+    }
+
+    // Copies the part of source that overlaps this chunk when placed at the offset
+    pub fn copy_from(&mut self, source: &Chunk, x_offset: usize, y_offset: usize) -> &mut Self {
+        let width = source.size.min(self.size.saturating_sub(x_offset));
+        let height = source.size.min(self.size.saturating_sub(y_offset));
+        if width == 0 {
+            return self;
+        }
+
+        for y in 0..height {
+            let start = self.index(x_offset, y + y_offset);
+            self.data[start..start + width].copy_from_slice(&source.row(y)[..width]);
         }
+
+        self
     }
 
-    pub fn add_data(&mut self, data: &Vec<Vec<f32>>, x_offset: usize, y_offset: usize) -> &mut Self {
-        for x in 0..data.len() {
-            for y in 0..data[x].len() {
-                self.data[x + x_offset][y + y_offset] = data[x][y];
-            }
+    // Blends the brush into the samples, its coverage ramping up over falloff inside the edge
+    pub fn paint<B: Brush + ?Sized>(&mut self, brush: &B, mode: BlendMode, falloff: f32) -> &mut Self {
+        self.paint_offset(brush, (0.0, 0.0), mode, falloff)
+    }
+
+    // Paints with the brush placed as if sample (0, 0) were at origin
+    pub fn paint_offset<B: Brush + ?Sized>(&mut self, brush: &B, origin: (f32, f32), mode: BlendMode, falloff: f32) -> &mut Self {
+        for (x, y, distance) in brush::covered_samples(brush, origin, self.size) {
+            let index = self.index(x, y);
+            self.data[index] = mode.blend(self.data[index], brush::coverage(distance, falloff));
         }
 
         self
     }
 
-    pub fn paint_antialiased_filled_circle(&mut self, x: f32, y: f32, radius: f32) {
-        let radius_squared = radius * radius;
-    
-        for i in 0..self.data.len() {
-            for j in 0..self.data[i].len() {
-                let distance_squared = (i as f32 - x).powf(2.0) + (j as f32 - y).powf(2.0);
-    
-                if distance_squared <= radius_squared {
-                    let distance = distance_squared.sqrt();
-                    let alpha = 1.0 - (distance - radius).abs() / 1.0;
-                    self.data[i as usize][j as usize] = alpha.max(0.0).min(self.data[i as usize][j as usize]);
-                }
-            }
+    pub fn paint_circle(&mut self, x: f32, y: f32, radius: f32, mode: BlendMode, falloff: f32) -> &mut Self {
+        self.paint(&Circle { centre: (x, y), radius }, mode, falloff)
+    }
+
+    pub fn paint_antialiased_filled_circle(&mut self, x: f32, y: f32, radius: f32) -> &mut Self {
+        self.paint_circle(x, y, radius, BlendMode::Carve, 1.0)
+    }
+
+    // Raises samples inside the circle, the opposite of painting it
+    pub fn fill_antialiased_filled_circle(&mut self, x: f32, y: f32, radius: f32) -> &mut Self {
+        self.paint_circle(x, y, radius, BlendMode::Fill, 1.0)
+    }
+
+    // Contours of this chunk alone, left open where they run off its edges
+    pub fn contours(&self, config: &MeshConfig) -> Vec<Contour> {
+        let mut builder = ContourBuilder::default();
+        let width = self.size.saturating_sub(1).max(1);
+
+        for (i, set) in SquareSet::chunk_to_sets(self).iter().enumerate() {
+            builder.add_set(set, (i % width) as i32, (i / width) as i32, config);
         }
+
+        builder.build()
+    }
+
+    pub fn to_image(&self, options: &ExportOptions) -> image::RgbImage {
+        image_io::render_image(
+            self.size,
+            self.size,
+            options,
+            |x, y| Some(self.get(x, y)),
+            |x, y| x == 0 || y == 0,
+        )
+    }
+
+    pub fn save_png(&self, path: impl AsRef<std::path::Path>, options: &ExportOptions) -> image::ImageResult<()> {
+        self.to_image(options).save_with_format(path, image::ImageFormat::Png)
     }
 
     pub fn print(&self) {
-        for row in self.data.iter() {
+        for row in self.rows() {
             for value in row {
                 print!("{} ", value);
             }
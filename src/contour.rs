@@ -0,0 +1,101 @@
+use std::collections::{ HashMap, HashSet };
+
+use crate::mesh::MeshConfig;
+use crate::square_march::{ GridKey, Point, SquareSet };
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Contour {
+    // Ordered with the inside (samples above the iso value) on the left
+    pub points: Vec<[f32; 2]>,
+    // Closed contours repeat nothing, the last point joins back to the first
+    pub closed: bool,
+}
+
+impl Contour {
+    pub fn signed_area(&self) -> f32 {
+        let mut area = 0.0;
+
+        for (i, from) in self.points.iter().enumerate() {
+            let to = self.points[(i + 1) % self.points.len()];
+            area += from[0] * to[1] - to[0] * from[1];
+        }
+
+        area / 2.0
+    }
+
+    // Closed outer boundaries wind anticlockwise, holes clockwise
+    pub fn is_outer(&self) -> bool {
+        self.closed && self.signed_area() > 0.0
+    }
+
+    pub fn is_hole(&self) -> bool {
+        self.closed && self.signed_area() < 0.0
+    }
+}
+
+// Stitches the contour segments of many sets together through the grid sides they share
+#[derive(Default)]
+pub(crate) struct ContourBuilder {
+    next: HashMap<GridKey, GridKey>,
+    positions: HashMap<GridKey, [f32; 2]>,
+}
+
+impl ContourBuilder {
+    // Adds a set whose a sample sits at (x, y)
+    pub fn add_set(&mut self, set: &SquareSet, x: i32, y: i32, config: &MeshConfig) {
+        for (from, to) in set.contour_sides(config) {
+            let from_key = self.add_side(set, from, x, y, config);
+            let to_key = self.add_side(set, to, x, y, config);
+            self.next.insert(from_key, to_key);
+        }
+    }
+
+    fn add_side(&mut self, set: &SquareSet, side: Point, x: i32, y: i32, config: &MeshConfig) -> GridKey {
+        let key = side.grid_key(x, y);
+
+        let local = side.position(set, config);
+        self.positions.entry(key).or_insert([
+            x as f32 + 0.5 + local[0],
+            y as f32 + 0.5 + local[1],
+        ]);
+
+        key
+    }
+
+    pub fn build(mut self) -> Vec<Contour> {
+        let mut contours = vec![];
+
+        // Open contours start on a side nothing leads into
+        let targets: HashSet<GridKey> = self.next.values().copied().collect();
+        let mut starts: Vec<GridKey> = self.next.keys()
+            .filter(|key| !targets.contains(key))
+            .copied()
+            .collect();
+        starts.sort();
+
+        for start in starts {
+            contours.push(self.follow(start, false));
+        }
+
+        while let Some(start) = self.next.keys().min().copied() {
+            contours.push(self.follow(start, true));
+        }
+
+        contours
+    }
+
+    fn follow(&mut self, start: GridKey, closed: bool) -> Contour {
+        let mut points = vec![self.positions[&start]];
+        let mut current = start;
+
+        while let Some(next) = self.next.remove(&current) {
+            if next == start {
+                break;
+            }
+            points.push(self.positions[&next]);
+            current = next;
+        }
+
+        Contour { points, closed }
+    }
+}
@@ -0,0 +1,63 @@
+use image::DynamicImage;
+
+use crate::chunk::Chunk;
+use crate::image_io::{ ImageOptions, ImageSampler };
+use crate::noise::NoiseGenerator;
+
+// Fills chunks the first time a Plane needs them. The same seed and coordinate must
+// always give the same samples, or chunks generated at different times won't join up
+pub trait ChunkGenerator {
+    // Fills the chunk, whose first sample sits at world (coord.0 * size, coord.1 * size)
+    fn generate(&self, seed: i32, coord: (i32, i32), chunk: &mut Chunk);
+}
+
+fn chunk_origin(coord: (i32, i32), chunk: &Chunk) -> (i32, i32) {
+    let size = chunk.size() as i32;
+    (coord.0 * size, coord.1 * size)
+}
+
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Empty;
+
+impl ChunkGenerator for Empty {
+    fn generate(&self, _seed: i32, _coord: (i32, i32), chunk: &mut Chunk) {
+        chunk.as_mut_slice().fill(0.0);
+    }
+}
+
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Solid;
+
+impl ChunkGenerator for Solid {
+    fn generate(&self, _seed: i32, _coord: (i32, i32), chunk: &mut Chunk) {
+        chunk.as_mut_slice().fill(1.0);
+    }
+}
+
+impl ChunkGenerator for NoiseGenerator {
+    fn generate(&self, seed: i32, coord: (i32, i32), chunk: &mut Chunk) {
+        let origin = chunk_origin(coord, chunk);
+        self.fill(seed, (origin.0 as f32, origin.1 as f32), chunk);
+    }
+}
+
+// Density read from an image placed in the world by its options
+#[derive(Clone, Debug)]
+pub struct ImageGenerator {
+    pub sampler: ImageSampler,
+}
+
+impl ImageGenerator {
+    pub fn new(image: &DynamicImage, options: ImageOptions) -> Self {
+        Self {
+            sampler: ImageSampler::new(image, options),
+        }
+    }
+}
+
+impl ChunkGenerator for ImageGenerator {
+    fn generate(&self, _seed: i32, coord: (i32, i32), chunk: &mut Chunk) {
+        let origin = chunk_origin(coord, chunk);
+        self.sampler.fill((origin.0 as f32, origin.1 as f32), chunk);
+    }
+}
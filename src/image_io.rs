@@ -0,0 +1,229 @@
+use image::{ DynamicImage, Rgb, RgbImage };
+
+use crate::chunk::Chunk;
+
+// Which part of each pixel becomes density
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum Channel {
+    #[default]
+    Luminance,
+    Red,
+    Green,
+    Blue,
+    Alpha,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ImageOptions {
+    pub channel: Channel,
+    // Sample position of the image's first pixel
+    pub origin: (f32, f32),
+    // Samples per pixel
+    pub scale: f32,
+    // Dark pixels become solid instead of white ones
+    pub invert: bool,
+    // Images store their top row first, flipping puts it at the largest y instead
+    pub flip_y: bool,
+    // Blend the four nearest pixels rather than taking the nearest one
+    pub bilinear: bool,
+    // Density of samples the image doesn't cover
+    pub outside: f32,
+}
+
+impl Default for ImageOptions {
+    fn default() -> Self {
+        Self {
+            channel: Channel::default(),
+            origin: (0.0, 0.0),
+            scale: 1.0,
+            invert: false,
+            flip_y: false,
+            bilinear: true,
+            outside: 0.0,
+        }
+    }
+}
+
+// One channel of an image as density, ready to be sampled at world positions
+#[derive(Clone, Debug)]
+pub struct ImageSampler {
+    width: usize,
+    height: usize,
+    // Row by row like Chunk, inversion already applied
+    values: Vec<f32>,
+    options: ImageOptions,
+}
+
+impl ImageSampler {
+    pub fn new(image: &DynamicImage, options: ImageOptions) -> Self {
+        assert!(options.scale > 0.0, "image scale must be positive");
+
+        let values: Vec<f32> = match options.channel {
+            Channel::Luminance => image.to_luma32f().into_raw(),
+            channel => {
+                let offset = match channel {
+                    Channel::Red => 0,
+                    Channel::Green => 1,
+                    Channel::Blue => 2,
+                    _ => 3,
+                };
+                image.to_rgba32f().pixels().map(|pixel| pixel.0[offset]).collect()
+            },
+        };
+
+        Self {
+            width: image.width() as usize,
+            height: image.height() as usize,
+            values: values.into_iter()
+                .map(|value| if options.invert { 1.0 - value } else { value })
+                .collect(),
+            options,
+        }
+    }
+
+    pub fn options(&self) -> &ImageOptions {
+        &self.options
+    }
+
+    // Smallest and largest world positions the image covers
+    pub fn bounds(&self) -> ((f32, f32), (f32, f32)) {
+        let (origin, scale) = (self.options.origin, self.options.scale);
+        (
+            (origin.0 - 0.5 * scale, origin.1 - 0.5 * scale),
+            (origin.0 + (self.width as f32 - 0.5) * scale, origin.1 + (self.height as f32 - 0.5) * scale),
+        )
+    }
+
+    fn pixel(&self, x: usize, y: usize) -> f32 {
+        let y = if self.options.flip_y { self.height - 1 - y } else { y };
+        self.values[y * self.width + x]
+    }
+
+    // Density at the world position, pixel centres sit at origin + (x, y) * scale
+    pub fn density(&self, x: f32, y: f32) -> f32 {
+        let pixel_x = (x - self.options.origin.0) / self.options.scale;
+        let pixel_y = (y - self.options.origin.1) / self.options.scale;
+
+        let (width, height) = (self.width as f32, self.height as f32);
+        if !(-0.5..width - 0.5).contains(&pixel_x) || !(-0.5..height - 0.5).contains(&pixel_y) {
+            return self.options.outside;
+        }
+
+        if !self.options.bilinear {
+            return self.pixel(pixel_x.round() as usize, pixel_y.round() as usize);
+        }
+
+        // Past the outer pixel centres the edge pixels are held
+        let pixel_x = pixel_x.clamp(0.0, width - 1.0);
+        let pixel_y = pixel_y.clamp(0.0, height - 1.0);
+        let (left, top) = (pixel_x.floor() as usize, pixel_y.floor() as usize);
+        let (right, bottom) = ((left + 1).min(self.width - 1), (top + 1).min(self.height - 1));
+        let (tx, ty) = (pixel_x.fract(), pixel_y.fract());
+
+        let upper = self.pixel(left, top) + (self.pixel(right, top) - self.pixel(left, top)) * tx;
+        let lower = self.pixel(left, bottom) + (self.pixel(right, bottom) - self.pixel(left, bottom)) * tx;
+
+        upper + (lower - upper) * ty
+    }
+
+    // Samples the image into the chunk, its first sample taken at origin
+    pub fn fill(&self, origin: (f32, f32), chunk: &mut Chunk) {
+        let size = chunk.size();
+
+        for (i, value) in chunk.as_mut_slice().iter_mut().enumerate() {
+            let (x, y) = ((i % size) as f32, (i / size) as f32);
+            *value = self.density(origin.0 + x, origin.1 + y);
+        }
+    }
+}
+
+// Colours for density when exporting, from 0 at the first stop to 1 at the last
+#[derive(Clone, Debug, Default, PartialEq)]
+pub enum ColourRamp {
+    // Black empty, white solid, so exports load back unchanged
+    #[default]
+    Grayscale,
+    // Colours at ascending densities, blended linearly between them
+    Stops(Vec<(f32, [u8; 3])>),
+}
+
+impl ColourRamp {
+    // Dark blue through green to white, with a sharp step at the iso value
+    pub fn terrain(iso: f32) -> Self {
+        ColourRamp::Stops(vec![
+            (0.0, [8, 16, 48]),
+            (iso, [40, 80, 160]),
+            (iso + 0.001, [60, 120, 40]),
+            (1.0, [240, 240, 230]),
+        ])
+    }
+
+    pub fn colour(&self, value: f32) -> [u8; 3] {
+        let value = value.clamp(0.0, 1.0);
+
+        match self {
+            ColourRamp::Grayscale => [(value * 255.0).round() as u8; 3],
+            ColourRamp::Stops(stops) => {
+                let Some(&(_, first)) = stops.first() else { return [0; 3] };
+                let mut colour = first;
+
+                for pair in stops.windows(2) {
+                    let ((from_value, from), (to_value, to)) = (pair[0], pair[1]);
+                    if value >= to_value {
+                        colour = to;
+                    } else if value > from_value {
+                        let t = (value - from_value) / (to_value - from_value);
+                        colour = [0, 1, 2].map(|i| (from[i] as f32 + (to[i] as f32 - from[i] as f32) * t).round() as u8);
+                        break;
+                    }
+                }
+
+                colour
+            },
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ExportOptions {
+    pub ramp: ColourRamp,
+    // Colour of each chunk's first row and column, none to leave them as density
+    pub grid: Option<[u8; 3]>,
+    // Puts the largest y at the top of the image, matching ImageOptions::flip_y
+    pub flip_y: bool,
+    // Colour of samples in chunks that aren't loaded
+    pub missing: [u8; 3],
+}
+
+impl Default for ExportOptions {
+    fn default() -> Self {
+        Self {
+            ramp: ColourRamp::default(),
+            grid: None,
+            flip_y: false,
+            missing: [255, 0, 255],
+        }
+    }
+}
+
+// Image with one pixel per sample, sample giving the density at (x, y) from the first sample
+pub(crate) fn render_image(
+    width: usize,
+    height: usize,
+    options: &ExportOptions,
+    sample: impl Fn(usize, usize) -> Option<f32>,
+    on_grid: impl Fn(usize, usize) -> bool,
+) -> RgbImage {
+    RgbImage::from_fn(width as u32, height as u32, |pixel_x, pixel_y| {
+        let (x, y) = (pixel_x as usize, pixel_y as usize);
+        let y = if options.flip_y { height - 1 - y } else { y };
+
+        let colour = match (options.grid, sample(x, y)) {
+            (Some(grid), _) if on_grid(x, y) => grid,
+            (_, Some(value)) => options.ramp.colour(value),
+            (_, None) => options.missing,
+        };
+
+        Rgb(colour)
+    })
+}
@@ -0,0 +1,31 @@
+pub mod brush;
+pub mod camera;
+pub mod chunk;
+pub mod contour;
+pub mod generator;
+pub mod image_io;
+pub mod mesh;
+pub mod noise;
+pub mod plane;
+pub mod region;
+pub mod sdf;
+pub mod square_march;
+pub mod world;
+#[cfg(feature = "viewer")]
+pub mod wgpuinit;
+
+pub use brush::{BlendMode, Brush, Capsule, Circle, Polygon, Rectangle, Ring};
+pub use camera::Camera;
+pub use chunk::Chunk;
+pub use contour::Contour;
+pub use generator::{ChunkGenerator, Empty, ImageGenerator, Solid};
+pub use image_io::{Channel, ColourRamp, ExportOptions, ImageOptions, ImageSampler};
+pub use mesh::{Interpolation, Mesh, MeshConfig, Meshable, Vertex};
+pub use noise::{NoiseGenerator, NoiseKind};
+pub use plane::{ChunkListener, Plane, ReferencePoint};
+pub use region::RegionStore;
+pub use sdf::{CsgOp, SdfChunk};
+pub use square_march::{SaddleRule, SquareSet};
+pub use world::WorldError;
+#[cfg(feature = "viewer")]
+pub use wgpuinit::run;
@@ -1,17 +1,9 @@
-mod wgpuinit;
-use wgpuinit::run;
-mod chunk;
-use chunk::Chunk;
-mod square_march;
-use square_march::SquareSet;
-mod mesh;
-use mesh::{Mesh, Meshable, Vertex};
-mod plane;
-use plane::{Plane, ReferencePoint};
-use std::collections::HashMap;
-use winit::{
-    event::*
-};
+use marching_squares_rust_opengl::{run, Plane, RegionStore, WorldError};
+
+// Where F5 saves the world
+const SAVE_PATH: &str = "world.msqw";
+// Where chunks are streamed to and from while exploring
+const REGION_PATH: &str = "regions";
 
 fn pattern_width(x: i32) -> i32 {
     if x == 0 {
@@ -20,62 +12,45 @@ fn pattern_width(x: i32) -> i32 {
     2_i32.pow(x as u32) + pattern_width(x - 1)
 }
 
-pub fn gen_controls() -> (fn(&WindowEvent) -> bool) {
-    return |event: &WindowEvent| {
-        match event {
-            WindowEvent::KeyboardInput {
-                input: KeyboardInput {
-                    state: ElementState::Pressed,
-                    virtual_keycode: Some(virtual_keycode),..
-                },..
-            } => match virtual_keycode {
-                VirtualKeyCode::Up => {
-                    
-                    true
+fn main() {
+    // Streamed regions are newer than any save, otherwise carry on from the last save and
+    // start streaming its chunks to new regions
+    let plane = match RegionStore::open(REGION_PATH) {
+        Ok(storage) => Plane::from_storage(storage).with_memory_budget(4096),
+        Err(WorldError::Io(error)) if error.kind() == std::io::ErrorKind::NotFound => {
+            let plane = saved_plane();
+            match RegionStore::new(REGION_PATH, plane.seed(), plane.chunk_size()) {
+                Ok(storage) => plane.with_storage(storage).with_memory_budget(4096),
+                Err(error) => {
+                    eprintln!("couldn't create {}, chunks won't be streamed: {}", REGION_PATH, error);
+                    plane
                 },
-                _ => false
-            },
-            _ => false 
-        }
-    }
+            }
+        },
+        Err(error) => {
+            eprintln!("couldn't open {}, chunks won't be streamed: {}", REGION_PATH, error);
+            saved_plane()
+        },
+    };
+
+    pollster::block_on(run(plane, SAVE_PATH));
 }
-fn input(event: &WindowEvent) -> bool {
-    let test = event;
-        match test {
-            WindowEvent::KeyboardInput {
-                input: KeyboardInput {
-                    state,
-                    virtual_keycode: Some(virtual_keycode),..
-                },..
-            } => {
-                println!("state: {0}, key: {1}", *state == ElementState::Pressed, match virtual_keycode {
-                    VirtualKeyCode::Up => "up_key",
-                    _ => "unkn"
-                });
-                return true
-            },
-            _ => return false
-        }
 
+fn saved_plane() -> Plane {
+    match Plane::load(SAVE_PATH) {
+        Ok(plane) => plane,
+        Err(WorldError::Io(error)) if error.kind() == std::io::ErrorKind::NotFound => demo_plane(),
+        Err(error) => {
+            eprintln!("couldn't load {}: {}", SAVE_PATH, error);
+            demo_plane()
+        },
+    }
 }
 
-fn main() {
+fn demo_plane() -> Plane {
     let mut plane = Plane::new();
 
-    let mut chunk = Chunk::new(32);
-
-    let mut reference = ReferencePoint {
-        position: (0.0, 0.0),
-        render_dist: 32.0
-    };
-
-    //chunk.paint_antialiased_filled_circle(10.0, 10.0, 6.0);
-
-    //chunk.print();
-
-    //plane.set_chunk((0, 0), chunk.clone());
     let size = 6;
-    //plane.clone_area(&chunk, 0, 0, pattern_width(size + 1), pattern_width(size + 1));
 
     for i in 1..=size {
         plane.paint_antialiased_filled_circle(
@@ -85,29 +60,5 @@ fn main() {
         );
     }
 
-    let zoom = 1.0;
-    //plane.paint_antialiased_filled_circle(521.0, 512.0, 256.0);
-
-    //let sets = SquareSet::chunk_to_sets(&chunk);
-
-    //let mut mesh: Mesh = plane.clone().mesh_from_ref(reference);/*ReferencePoint {
-    //    position: (52.0, 52.0),
-     //    render_dist: 32.0 * zoom,
-    //});*/
-
-    //for vertex in &mesh.vertices {
-    //    println!(
-    //        "v{0}, {1}, {2}",
-    //        vertex.position[0], vertex.position[1], vertex.position[2]
-    //    );
-    //}
-    //for a in mesh.indices.windows(3) {
-    //    println!("i{0}, {1}, {2}", a[0], a[1], a[2]);
-    //}
-
-    //println!("{}", mesh.vertices.len());
-
-    //mesh.scale(1.0 / zoom);
-
-    pollster::block_on(run(plane));
+    plane
 }
@@ -1,3 +1,5 @@
+use crate::square_march::SaddleRule;
+
 #[repr(C)]
 #[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
 pub struct Vertex {
@@ -51,7 +53,7 @@ impl Vertex {
 #[derive(Clone)]
 pub struct Mesh {
     pub vertices: Vec<Vertex>,
-    pub indices: Vec<i16>
+    pub indices: Vec<u32>
 }
 
 impl Mesh {
@@ -90,23 +92,57 @@ impl Mesh {
     }
 
     pub fn union(&mut self, mesh: &Mesh) -> &mut Self {
-        let index_offset = self.vertices.len() as i16;
+        assert!(
+            self.vertices.len() + mesh.vertices.len() <= u32::MAX as usize + 1,
+            "mesh union has more vertices than u32 indices can address"
+        );
+        let index_offset = self.vertices.len() as u32;
         self.vertices.extend(&mesh.vertices);
         for index in &mesh.indices {
             self.indices.push(index + index_offset);
         }
 
         self
-        //let index_offset = self.vertices.len() as i16;
-        //self.vertices.extend(&mesh.vertices);
-        //for index in &mesh.indices {
-        //    self.indices.push(index + index_offset);
-        //}
-        //
-        //self
+    }
+
+    // The indices as u16 when every vertex can be addressed by one, halving the upload size
+    pub fn compact_indices(&self) -> Option<Vec<u16>> {
+        if self.vertices.len() > u16::MAX as usize + 1 {
+            return None;
+        }
+
+        Some(self.indices.iter().map(|&index| index as u16).collect())
+    }
+}
+
+// How edge vertices are placed between two samples either side of the iso value
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum Interpolation {
+    // Crossing point eased towards the nearer sample
+    #[default]
+    Smooth,
+    // Crossing point exactly where the line between the samples meets the iso value
+    Linear,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct MeshConfig {
+    // Samples above this value are inside the mesh
+    pub iso: f32,
+    pub interpolation: Interpolation,
+    pub saddle: SaddleRule,
+}
+
+impl Default for MeshConfig {
+    fn default() -> Self {
+        Self {
+            iso: 0.2,
+            interpolation: Interpolation::default(),
+            saddle: SaddleRule::default(),
+        }
     }
 }
 
 pub trait Meshable {
-    fn to_mesh(&self) -> Mesh;
+    fn to_mesh(&self, config: &MeshConfig) -> Mesh;
 }
@@ -0,0 +1,236 @@
+use crate::chunk::Chunk;
+
+// Lattice noise functions, each giving values in about -1..1 that vary over one unit
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum NoiseKind {
+    // Interpolated random values at each lattice point, blocky at low octaves
+    Value,
+    // Interpolated random gradients on a square lattice
+    #[default]
+    Perlin,
+    // Random gradients on a triangular lattice, with fewer axis aligned artefacts
+    Simplex,
+}
+
+impl NoiseKind {
+    pub fn sample(self, seed: i32, x: f32, y: f32) -> f32 {
+        match self {
+            NoiseKind::Value => value(seed, x, y),
+            NoiseKind::Perlin => perlin(seed, x, y),
+            NoiseKind::Simplex => simplex(seed, x, y),
+        }
+    }
+}
+
+// Fractal noise turned into density. Everything is derived from the seed and world
+// position with integer hashing, so chunks join seamlessly and match on every machine
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct NoiseGenerator {
+    pub kind: NoiseKind,
+    // Lattice cells per sample of the first octave
+    pub frequency: f32,
+    pub octaves: u32,
+    // Frequency multiplier between octaves
+    pub lacunarity: f32,
+    // Amplitude multiplier between octaves
+    pub gain: f32,
+    // Distance in samples a second noise pushes positions around, 0 for none
+    pub warp: f32,
+    // Noise above the threshold is solid and below it empty, blended across the width.
+    // Without it density follows the noise smoothly
+    pub cave: Option<(f32, f32)>,
+}
+
+impl Default for NoiseGenerator {
+    fn default() -> Self {
+        Self {
+            kind: NoiseKind::default(),
+            frequency: 1.0 / 32.0,
+            octaves: 4,
+            lacunarity: 2.0,
+            gain: 0.5,
+            warp: 0.0,
+            cave: None,
+        }
+    }
+}
+
+impl NoiseGenerator {
+    pub fn new(kind: NoiseKind) -> Self {
+        Self {
+            kind,
+            ..Self::default()
+        }
+    }
+
+    pub fn frequency(mut self, frequency: f32) -> Self {
+        self.frequency = frequency;
+
+        self
+    }
+
+    pub fn octaves(mut self, octaves: u32, lacunarity: f32, gain: f32) -> Self {
+        self.octaves = octaves;
+        self.lacunarity = lacunarity;
+        self.gain = gain;
+
+        self
+    }
+
+    pub fn warp(mut self, warp: f32) -> Self {
+        self.warp = warp;
+
+        self
+    }
+
+    pub fn caves(mut self, threshold: f32, width: f32) -> Self {
+        self.cave = Some((threshold, width));
+
+        self
+    }
+
+    // Sum of octaves, scaled back into about -1..1
+    pub fn fractal(&self, seed: i32, x: f32, y: f32) -> f32 {
+        let mut total = 0.0;
+        let mut amplitude = 1.0;
+        let mut amplitudes = 0.0;
+        let mut frequency = self.frequency;
+
+        for octave in 0..self.octaves.max(1) {
+            let octave_seed = seed.wrapping_add(octave as i32);
+            total += self.kind.sample(octave_seed, x * frequency, y * frequency) * amplitude;
+            amplitudes += amplitude;
+            amplitude *= self.gain;
+            frequency *= self.lacunarity;
+        }
+
+        total / amplitudes
+    }
+
+    // Density at the world position, from 0 (empty) to 1 (solid)
+    pub fn density(&self, seed: i32, x: f32, y: f32) -> f32 {
+        let (mut x, mut y) = (x, y);
+        if self.warp != 0.0 {
+            // Offsets come from their own seeds so they don't correlate with the terrain
+            let warp_x = self.kind.sample(seed ^ 0x5f3759df, x * self.frequency, y * self.frequency);
+            let warp_y = self.kind.sample(seed ^ 0x1b873593, x * self.frequency, y * self.frequency);
+            x += warp_x * self.warp;
+            y += warp_y * self.warp;
+        }
+
+        let noise = self.fractal(seed, x, y);
+
+        match self.cave {
+            Some((threshold, width)) if width > 0.0 => (0.5 + (noise - threshold) / width).clamp(0.0, 1.0),
+            Some((threshold, _)) => if noise > threshold { 1.0 } else { 0.0 },
+            None => (0.5 + 0.5 * noise).clamp(0.0, 1.0),
+        }
+    }
+
+    // Fills the chunk with its first sample at origin, in world samples
+    pub fn fill(&self, seed: i32, origin: (f32, f32), chunk: &mut Chunk) {
+        let size = chunk.size();
+
+        for (i, value) in chunk.as_mut_slice().iter_mut().enumerate() {
+            let (x, y) = ((i % size) as f32, (i / size) as f32);
+            *value = self.density(seed, origin.0 + x, origin.1 + y);
+        }
+    }
+}
+
+// Well mixed 32 bits from a seed and lattice point
+fn hash(seed: i32, x: i32, y: i32) -> u32 {
+    let mut hash = seed as u32;
+    for value in [x as u32, y as u32] {
+        hash ^= value.wrapping_mul(0x9e3779b1);
+        hash ^= hash >> 16;
+        hash = hash.wrapping_mul(0x85ebca6b);
+        hash ^= hash >> 13;
+        hash = hash.wrapping_mul(0xc2b2ae35);
+        hash ^= hash >> 16;
+    }
+
+    hash
+}
+
+// Uniform in -1..1
+fn random(seed: i32, x: i32, y: i32) -> f32 {
+    (hash(seed, x, y) >> 8) as f32 / (1 << 23) as f32 - 1.0
+}
+
+const GRADIENTS: [(f32, f32); 8] = [
+    (1.0, 0.0), (-1.0, 0.0), (0.0, 1.0), (0.0, -1.0),
+    (0.70710677, 0.70710677), (-0.70710677, 0.70710677), (0.70710677, -0.70710677), (-0.70710677, -0.70710677),
+];
+
+fn gradient(seed: i32, x: i32, y: i32, dx: f32, dy: f32) -> f32 {
+    let (gx, gy) = GRADIENTS[(hash(seed, x, y) >> 29) as usize];
+    gx * dx + gy * dy
+}
+
+// Quintic ease, so the noise has no creases at lattice lines
+fn fade(t: f32) -> f32 {
+    t * t * t * (t * (t * 6.0 - 15.0) + 10.0)
+}
+
+fn lerp(a: f32, b: f32, t: f32) -> f32 {
+    a + (b - a) * t
+}
+
+fn value(seed: i32, x: f32, y: f32) -> f32 {
+    let (cell_x, cell_y) = (x.floor(), y.floor());
+    let (ix, iy) = (cell_x as i32, cell_y as i32);
+    let (tx, ty) = (fade(x - cell_x), fade(y - cell_y));
+
+    lerp(
+        lerp(random(seed, ix, iy), random(seed, ix + 1, iy), tx),
+        lerp(random(seed, ix, iy + 1), random(seed, ix + 1, iy + 1), tx),
+        ty,
+    )
+}
+
+fn perlin(seed: i32, x: f32, y: f32) -> f32 {
+    let (cell_x, cell_y) = (x.floor(), y.floor());
+    let (ix, iy) = (cell_x as i32, cell_y as i32);
+    let (dx, dy) = (x - cell_x, y - cell_y);
+    let (tx, ty) = (fade(dx), fade(dy));
+
+    let noise = lerp(
+        lerp(gradient(seed, ix, iy, dx, dy), gradient(seed, ix + 1, iy, dx - 1.0, dy), tx),
+        lerp(gradient(seed, ix, iy + 1, dx, dy - 1.0), gradient(seed, ix + 1, iy + 1, dx - 1.0, dy - 1.0), tx),
+        ty,
+    );
+
+    // Unit gradients peak at sqrt(0.5)
+    (noise * std::f32::consts::SQRT_2).clamp(-1.0, 1.0)
+}
+
+fn simplex(seed: i32, x: f32, y: f32) -> f32 {
+    const SKEW: f32 = 0.3660254; // (sqrt(3) - 1) / 2
+    const UNSKEW: f32 = 0.21132487; // (3 - sqrt(3)) / 6
+
+    // Find the triangle holding the point in the skewed lattice
+    let skew = (x + y) * SKEW;
+    let (cell_x, cell_y) = ((x + skew).floor(), (y + skew).floor());
+    let (ix, iy) = (cell_x as i32, cell_y as i32);
+
+    let unskew = (cell_x + cell_y) * UNSKEW;
+    let (x0, y0) = (x - (cell_x - unskew), y - (cell_y - unskew));
+    let (step_x, step_y) = if x0 > y0 { (1, 0) } else { (0, 1) };
+
+    let corners = [
+        (0, 0, x0, y0),
+        (step_x, step_y, x0 - step_x as f32 + UNSKEW, y0 - step_y as f32 + UNSKEW),
+        (1, 1, x0 - 1.0 + 2.0 * UNSKEW, y0 - 1.0 + 2.0 * UNSKEW),
+    ];
+
+    let mut noise = 0.0;
+    for (corner_x, corner_y, dx, dy) in corners {
+        let falloff = 0.5 - dx * dx - dy * dy;
+        if falloff > 0.0 {
+            noise += falloff.powi(4) * gradient(seed, ix + corner_x, iy + corner_y, dx, dy);
+        }
+    }
+
+    (noise * 99.0).clamp(-1.0, 1.0)
+}
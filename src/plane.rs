@@ -1,4 +1,18 @@
-use crate::{ Chunk, Mesh, Meshable, HashMap, SquareSet, WindowEvent, ElementState, VirtualKeyCode };
+use std::collections::{ HashMap, HashSet };
+use std::fs::File;
+use std::io::{ BufReader, BufWriter, Read, Write };
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::brush::{ self, BlendMode, Brush, Circle };
+use crate::chunk::Chunk;
+use crate::contour::{ Contour, ContourBuilder };
+use crate::generator::{ ChunkGenerator, ImageGenerator, Solid };
+use crate::image_io::{ self, ExportOptions, ImageOptions };
+use crate::mesh::{ Mesh, MeshConfig, Meshable };
+use crate::region::RegionStore;
+use crate::square_march::SquareSet;
+use crate::world::{ self, Header, WorldError };
 
 #[repr(C)]
 #[derive(Clone)]
@@ -7,95 +21,629 @@ pub struct ReferencePoint {
     pub render_dist: f32,
 }
 
+// Told when Plane::update_active loads or unloads chunks, so a renderer can keep up
+pub trait ChunkListener {
+    fn chunk_loaded(&mut self, _coord: (i32, i32)) {}
+    fn chunk_unloaded(&mut self, _coord: (i32, i32)) {}
+}
+
+impl ChunkListener for () {}
+
 #[repr(C)]
 #[derive(Clone)]
 pub struct Plane {
     seed: i32,
-    chunks: HashMap<(i32, i32), Chunk>
+    chunk_size: usize,
+    chunks: HashMap<(i32, i32), Chunk>,
+    // Chunks whose cached mesh no longer matches their samples
+    dirty: HashSet<(i32, i32)>,
+    meshes: HashMap<(i32, i32), Mesh>,
+    mesh_config: Option<MeshConfig>,
+    // Fills chunks the first time they are needed, shared between clones of the plane
+    generator: Arc<dyn ChunkGenerator + Send + Sync>,
+    // Region files chunks are paged out to and back in from
+    storage: Option<RegionStore>,
+    // Chunks changed since they were loaded or last stored
+    unsaved: HashSet<(i32, i32)>,
+    // Most chunks kept loaded before the least recently used are paged out
+    memory_budget: Option<usize>,
+    last_used: HashMap<(i32, i32), u64>,
+    clock: u64,
+    // World distance past the render distance chunks stay loaded, so moving back and
+    // forth across a chunk border doesn't load and unload it every time
+    unload_margin: f32,
 }
 
 impl Plane {
     pub fn new() -> Self {
+        Self::with_chunk_size(32)
+    }
+
+    pub fn with_chunk_size(chunk_size: usize) -> Self {
+        assert!(chunk_size > 0, "chunk size must be at least one sample");
+
         Self {
             seed: 0,
-            chunks: HashMap::new()
+            chunk_size,
+            chunks: HashMap::new(),
+            dirty: HashSet::new(),
+            meshes: HashMap::new(),
+            mesh_config: None,
+            generator: Arc::new(Solid),
+            storage: None,
+            unsaved: HashSet::new(),
+            memory_budget: None,
+            last_used: HashMap::new(),
+            clock: 0,
+            unload_margin: chunk_size as f32,
         }
     }
 
-    pub fn get_chunk(&self, coord: (i32, i32)) -> Option<Chunk> {
-        self.chunks.clone().get(&coord).cloned()
+    // Loads every chunk the image covers, chunks generated later outside it get the options' outside value
+    pub fn from_image(image: &image::DynamicImage, chunk_size: usize, options: ImageOptions) -> Self {
+        let generator = ImageGenerator::new(image, options);
+        let (min, max) = generator.sampler.bounds();
+
+        let mut plane = Self::with_chunk_size(chunk_size).with_generator(generator);
+        let (min_chunk_x, min_chunk_y) = plane.world_to_chunk(min.0, min.1);
+        let (max_chunk_x, max_chunk_y) = plane.world_to_chunk(max.0, max.1);
+
+        for chunk_x in min_chunk_x..=max_chunk_x {
+            for chunk_y in min_chunk_y..=max_chunk_y {
+                plane.chunk_or_generate((chunk_x, chunk_y)).expect("planes without storage generate every chunk");
+            }
+        }
+
+        plane
     }
 
-    pub fn get_or_gen_chunk(&mut self, coord: (i32, i32)) -> Chunk {
-        match self.get_chunk(coord) {
-            Some(x) => return x,
-            None => match self.generate(coord) {
-                Some(x) => return x,
-                None => (),
-            },
+    pub fn with_seed(mut self, seed: i32) -> Self {
+        if let Some(storage) = &self.storage {
+            assert_eq!(storage.seed(), seed, "region store seed does not match the plane");
+        }
+        self.seed = seed;
+
+        self
+    }
+
+    pub fn with_generator(mut self, generator: impl ChunkGenerator + Send + Sync + 'static) -> Self {
+        self.generator = Arc::new(generator);
+
+        self
+    }
+
+    // Carries on from the chunks in storage, with the seed and chunk size they were written with
+    pub fn from_storage(storage: RegionStore) -> Self {
+        Self::with_chunk_size(storage.chunk_size()).with_seed(storage.seed()).with_storage(storage)
+    }
+
+    pub fn with_storage(mut self, storage: RegionStore) -> Self {
+        assert_eq!(storage.chunk_size(), self.chunk_size, "region store chunk size does not match the plane");
+        assert_eq!(storage.seed(), self.seed, "region store seed does not match the plane");
+        self.storage = Some(storage);
+
+        self
+    }
+
+    // The budget is only enforced while streaming, by update_active and stream, so their
+    // listeners hear about every unload. Chunks loaded any other way, by painting or reading
+    // them, can go over it until the next update. Without storage, chunks paged out to stay
+    // in budget lose their edits
+    pub fn with_memory_budget(mut self, chunks: usize) -> Self {
+        self.memory_budget = Some(chunks);
+
+        self
+    }
+
+    pub fn with_unload_margin(mut self, margin: f32) -> Self {
+        self.unload_margin = margin;
+
+        self
+    }
+
+    pub fn seed(&self) -> i32 {
+        self.seed
+    }
+
+    pub fn chunk_size(&self) -> usize {
+        self.chunk_size
+    }
+
+    // Chunk holding the sample at (x, y), rounding towards negative infinity
+    pub fn sample_to_chunk(&self, x: i32, y: i32) -> (i32, i32) {
+        let size = self.chunk_size as i32;
+        (x.div_euclid(size), y.div_euclid(size))
+    }
+
+    // Chunk holding the sample at (x, y) and the sample's index within it
+    pub fn sample_to_local(&self, x: i32, y: i32) -> ((i32, i32), (usize, usize)) {
+        let size = self.chunk_size as i32;
+        (self.sample_to_chunk(x, y), (x.rem_euclid(size) as usize, y.rem_euclid(size) as usize))
+    }
+
+    // Chunk holding the nearest sample at or below world position (x, y)
+    pub fn world_to_chunk(&self, x: f32, y: f32) -> (i32, i32) {
+        self.sample_to_chunk(x.floor() as i32, y.floor() as i32)
+    }
+
+    // Chunk containing world position (x, y) and the position relative to that chunk's origin
+    pub fn world_to_local(&self, x: f32, y: f32) -> ((i32, i32), (f32, f32)) {
+        let coord = self.world_to_chunk(x, y);
+        let origin = self.chunk_to_world(coord);
+
+        (coord, (x - origin.0, y - origin.1))
+    }
+
+    // World position of the chunk's first sample
+    pub fn chunk_to_world(&self, coord: (i32, i32)) -> (f32, f32) {
+        let size = self.chunk_size as i32;
+        ((coord.0 * size) as f32, (coord.1 * size) as f32)
+    }
+
+    pub fn sample(&self, x: i32, y: i32) -> Option<f32> {
+        let (coord, (local_x, local_y)) = self.sample_to_local(x, y);
+        self.chunk(coord).map(|chunk| chunk.get(local_x, local_y))
+    }
+
+    pub fn chunk(&self, coord: (i32, i32)) -> Option<&Chunk> {
+        self.chunks.get(&coord)
+    }
+
+    // Borrowing a chunk mutably marks it dirty, whether or not it is then changed
+    pub fn chunk_mut(&mut self, coord: (i32, i32)) -> Option<&mut Chunk> {
+        if self.chunks.contains_key(&coord) {
+            self.modified(coord);
         }
 
-        Chunk::new(32)
+        self.chunks.get_mut(&coord)
+    }
+
+    // Pages the chunk in from storage, or generates it if it was never stored, without
+    // counting it as changed. A stored chunk that can't be read is an error rather than
+    // generated again, so it is never overwritten
+    fn chunk_or_generate(&mut self, coord: (i32, i32)) -> Result<&Chunk, WorldError> {
+        self.page_in(coord)?;
+
+        Ok(&self.chunks[&coord])
+    }
+
+    // Like chunk_or_generate, but the chunk is marked changed for remeshing and saving
+    pub fn chunk_or_generate_mut(&mut self, coord: (i32, i32)) -> Result<&mut Chunk, WorldError> {
+        self.page_in(coord)?;
+        self.modified(coord);
+
+        Ok(self.chunks.get_mut(&coord).unwrap())
+    }
+
+    pub fn chunks(&self) -> impl Iterator<Item = ((i32, i32), &Chunk)> {
+        self.chunks.iter().map(|(coord, chunk)| (*coord, chunk))
+    }
+
+    // Loaded chunks with coordinates between min and max inclusive
+    pub fn chunks_in_region(&self, min: (i32, i32), max: (i32, i32)) -> impl Iterator<Item = ((i32, i32), &Chunk)> {
+        (min.1..=max.1)
+            .flat_map(move |y| (min.0..=max.0).map(move |x| (x, y)))
+            .filter_map(|coord| self.chunk(coord).map(|chunk| (coord, chunk)))
+    }
+
+    pub fn get_chunk(&self, coord: (i32, i32)) -> Option<Chunk> {
+        self.chunk(coord).cloned()
+    }
+
+    pub fn get_or_gen_chunk(&mut self, coord: (i32, i32)) -> Result<Chunk, WorldError> {
+        self.chunk_or_generate(coord).cloned()
     }
 
     pub fn set_chunk(&mut self, coord: (i32, i32), chunk: Chunk) -> Option<Chunk> {
-        self.chunks.insert(coord, chunk)
+        assert_eq!(chunk.size(), self.chunk_size, "chunk size does not match the plane");
+        let previous = self.chunks.insert(coord, chunk);
+        self.modified(coord);
+
+        previous
+    }
+
+    fn touch(&mut self, coord: (i32, i32)) {
+        self.clock += 1;
+        self.last_used.insert(coord, self.clock);
+    }
+
+    fn modified(&mut self, coord: (i32, i32)) {
+        self.mark_dirty(coord);
+        self.unsaved.insert(coord);
+        self.touch(coord);
+    }
+
+    // Loads the chunk from storage, or generates it, unless it is already loaded
+    pub fn page_in(&mut self, coord: (i32, i32)) -> Result<(), WorldError> {
+        if !self.chunks.contains_key(&coord) {
+            let stored = match &self.storage {
+                Some(storage) => storage.load_chunk(coord)?,
+                None => None,
+            };
+            let chunk = stored.unwrap_or_else(|| self.generate(coord));
+
+            self.chunks.insert(coord, chunk);
+            self.mark_dirty(coord);
+        }
+        self.touch(coord);
+
+        Ok(())
+    }
+
+    // Unloads the chunk, first writing it to storage if it changed
+    pub fn page_out(&mut self, coord: (i32, i32)) -> Result<Option<Chunk>, WorldError> {
+        if let (Some(storage), Some(chunk)) = (&self.storage, self.chunks.get(&coord)) {
+            if self.unsaved.contains(&coord) {
+                storage.save_chunk(coord, chunk)?;
+            }
+        }
+
+        // The neighbours' meshes included this chunk's edge
+        self.mark_dirty(coord);
+        self.dirty.remove(&coord);
+        self.meshes.remove(&coord);
+        self.unsaved.remove(&coord);
+        self.last_used.remove(&coord);
+
+        Ok(self.chunks.remove(&coord))
+    }
+
+    // Writes every changed chunk to storage
+    pub fn flush(&mut self) -> Result<(), WorldError> {
+        let Some(storage) = &self.storage else { return Ok(()) };
+
+        let mut unsaved: Vec<(i32, i32)> = self.unsaved.iter().copied().collect();
+        unsaved.sort();
+        for coord in unsaved {
+            if let Some(chunk) = self.chunks.get(&coord) {
+                storage.save_chunk(coord, chunk)?;
+            }
+            self.unsaved.remove(&coord);
+        }
+
+        Ok(())
+    }
+
+    // Pages out the least recently used chunks outside keep until the plane is within
+    // its memory budget, returning the chunks paged out
+    pub fn enforce_memory_budget(&mut self, keep: &HashSet<(i32, i32)>) -> Result<Vec<(i32, i32)>, WorldError> {
+        let Some(budget) = self.memory_budget else { return Ok(vec![]) };
+        if self.chunks.len() <= budget {
+            return Ok(vec![]);
+        }
+
+        let mut candidates: Vec<(i32, i32)> = self.chunks.keys()
+            .filter(|coord| !keep.contains(coord))
+            .copied()
+            .collect();
+        candidates.sort_by_key(|coord| self.last_used.get(coord).copied().unwrap_or(0));
+
+        let excess = self.chunks.len() - budget;
+        let mut paged_out = vec![];
+        for coord in candidates.into_iter().take(excess) {
+            self.page_out(coord)?;
+            paged_out.push(coord);
+        }
+
+        Ok(paged_out)
+    }
+
+    // Keeps the chunks around a single reference point loaded, nobody listening
+    pub fn stream(&mut self, ref_point: &ReferencePoint) -> Result<(), WorldError> {
+        self.update_active(std::slice::from_ref(ref_point), &mut ())
+    }
+
+    // Loads every chunk within the render distance of a reference point and unloads those
+    // further than the unload margin beyond it. Without storage, chunks with edits are kept
+    // loaded instead of being thrown away
+    pub fn update_active(&mut self, ref_points: &[ReferencePoint], listener: &mut dyn ChunkListener) -> Result<(), WorldError> {
+        let mut active = HashSet::new();
+        let mut keep = HashSet::new();
+
+        for ref_point in ref_points {
+            let (min, max) = self.ref_region(ref_point);
+            for x in min.0..=max.0 {
+                for y in min.1..=max.1 {
+                    active.insert((x, y));
+                }
+            }
+
+            let (min, max) = self.ref_region(&ReferencePoint {
+                render_dist: ref_point.render_dist + self.unload_margin,
+                ..ref_point.clone()
+            });
+            for x in min.0..=max.0 {
+                for y in min.1..=max.1 {
+                    keep.insert((x, y));
+                }
+            }
+        }
+
+        let mut entering: Vec<(i32, i32)> = active.iter().copied().collect();
+        entering.sort();
+        for coord in entering {
+            let loaded = self.chunks.contains_key(&coord);
+            self.page_in(coord)?;
+            if !loaded {
+                listener.chunk_loaded(coord);
+            }
+        }
+
+        let mut leaving: Vec<(i32, i32)> = self.chunks.keys()
+            .filter(|coord| !keep.contains(coord))
+            .filter(|coord| self.storage.is_some() || !self.unsaved.contains(coord))
+            .copied()
+            .collect();
+        leaving.sort();
+        for coord in leaving {
+            self.page_out(coord)?;
+            listener.chunk_unloaded(coord);
+        }
+
+        for coord in self.enforce_memory_budget(&active)? {
+            listener.chunk_unloaded(coord);
+        }
+
+        Ok(())
+    }
+    // Marks the chunk and the neighbours whose meshes include its first row or column
+    pub fn mark_dirty(&mut self, coord: (i32, i32)) {
+        for neighbour in [(0, 0), (-1, 0), (0, -1), (-1, -1)] {
+            let neighbour = (coord.0 + neighbour.0, coord.1 + neighbour.1);
+            if self.chunks.contains_key(&neighbour) {
+                self.dirty.insert(neighbour);
+            }
+        }
+    }
+
+    pub fn is_dirty(&self, coord: (i32, i32)) -> bool {
+        self.dirty.contains(&coord)
     }
 
-    fn generate(&mut self, coord: (i32, i32)) -> Option<Chunk> {
-        self.set_chunk(coord, Chunk::new(32)) as Option<Chunk>
+    fn generate(&self, coord: (i32, i32)) -> Chunk {
+        let mut chunk = Chunk::new(self.chunk_size);
+        self.generator.generate(self.seed, coord, &mut chunk);
+
+        chunk
     }
         
     pub fn total_chunks(&self) -> usize {
         self.chunks.iter().len()
     }
 
-    pub fn mesh_from_ref(&self, ref_point: &ReferencePoint) -> Mesh {
-        let scale = 32.0;
+    // The chunk with the first row and column of its neighbours appended, so
+    // the sets along its far edges join up with the next chunks
+    fn edged_chunk(&self, coord: (i32, i32)) -> Option<Chunk> {
+        let chunk = self.chunk(coord)?;
 
-        let mut meshes: Vec<Mesh> = vec![];
+        let size = self.chunk_size;
+        let mut edged_chunk = Chunk::new(size + 1);
+        edged_chunk.copy_from(chunk, 0, 0);
+        if let Some(v) = self.chunk((coord.0 + 1, coord.1)) {
+            edged_chunk.copy_from(v, size, 0);
+        }
+        if let Some(v) = self.chunk((coord.0, coord.1 + 1)) {
+            edged_chunk.copy_from(v, 0, size);
+        }
+        if let Some(v) = self.chunk((coord.0 + 1, coord.1 + 1)) {
+            edged_chunk.copy_from(v, size, size);
+        }
+
+        Some(edged_chunk)
+    }
+
+    // Sets of the edged chunk, row by row. Edged chunks pad missing neighbours, which isn't
+    // data to mesh or contour, so sets reaching into them are left with nothing inside
+    fn edged_sets(&self, coord: (i32, i32)) -> Option<Vec<SquareSet>> {
+        let edged_chunk = self.edged_chunk(coord)?;
+        let size = self.chunk_size;
+
+        let right = self.chunk((coord.0 + 1, coord.1)).is_some();
+        let below = self.chunk((coord.0, coord.1 + 1)).is_some();
+        let corner = self.chunk((coord.0 + 1, coord.1 + 1)).is_some();
+        let outside = SquareSet { a: f32::NEG_INFINITY, b: f32::NEG_INFINITY, d: f32::NEG_INFINITY, c: f32::NEG_INFINITY };
+
+        let mut sets = SquareSet::chunk_to_sets(&edged_chunk);
+        for (i, set) in sets.iter_mut().enumerate() {
+            let (last_column, last_row) = (i % size == size - 1, i / size == size - 1);
+            if (last_column && !right) || (last_row && !below) || (last_column && last_row && !corner) {
+                *set = outside;
+            }
+        }
+
+        Some(sets)
+    }
+
+    // Contours of every loaded chunk in sample coordinates, joined across chunk edges.
+    // Contours reaching chunks that aren't loaded stay open at the last loaded samples
+    pub fn contours(&self, config: &MeshConfig) -> Vec<Contour> {
+        let mut builder = ContourBuilder::default();
+        let size = self.chunk_size;
+
+        for &(x, y) in self.chunks.keys() {
+            let Some(sets) = self.edged_sets((x, y)) else { continue };
+
+            for (i, set) in sets.iter().enumerate() {
+                builder.add_set(set, x * size as i32 + (i % size) as i32, y * size as i32 + (i / size) as i32, config);
+            }
+        }
+
+        builder.build()
+    }
+
+    fn build_chunk_mesh(&self, coord: (i32, i32), config: &MeshConfig) -> Option<Mesh> {
+        let sets = self.edged_sets(coord)?;
+
+        let origin = self.chunk_to_world(coord);
+        let mut chunk_mesh = sets.to_mesh(config);
+        chunk_mesh.translate([origin.0, origin.1, 0.0]);
+
+        Some(chunk_mesh)
+    }
+
+    // Meshes are cached for one config at a time, changing it discards them
+    fn use_mesh_config(&mut self, config: &MeshConfig) {
+        if self.mesh_config.as_ref() != Some(config) {
+            self.meshes.clear();
+            self.mesh_config = Some(*config);
+        }
+    }
+
+    // Mesh of one chunk in world units, rebuilt only if the chunk is dirty or not cached yet
+    pub fn chunk_mesh(&mut self, coord: (i32, i32), config: &MeshConfig) -> Option<&Mesh> {
+        self.use_mesh_config(config);
+
+        if self.dirty.remove(&coord) || !self.meshes.contains_key(&coord) {
+            match self.build_chunk_mesh(coord, config) {
+                Some(mesh) => self.meshes.insert(coord, mesh),
+                None => self.meshes.remove(&coord),
+            };
+        }
+
+        self.meshes.get(&coord)
+    }
+
+    // Rebuilds the cached mesh of every dirty chunk, returning the chunks that were remeshed
+    pub fn remesh_dirty(&mut self, config: &MeshConfig) -> Vec<(i32, i32)> {
+        self.use_mesh_config(config);
+
+        let dirty: Vec<(i32, i32)> = self.dirty.drain().collect();
+        for coord in dirty.iter() {
+            match self.build_chunk_mesh(*coord, config) {
+                Some(mesh) => self.meshes.insert(*coord, mesh),
+                None => self.meshes.remove(coord),
+            };
+        }
+
+        dirty
+    }
+
+    // Range of chunks within the reference point's render distance
+    pub fn ref_region(&self, ref_point: &ReferencePoint) -> ((i32, i32), (i32, i32)) {
+        let (x, y) = ref_point.position;
+
+        (
+            self.world_to_chunk(x - ref_point.render_dist, y - ref_point.render_dist),
+            self.world_to_chunk(x + ref_point.render_dist, y + ref_point.render_dist),
+        )
+    }
+
+    // Mesh in world units of every chunk within the reference point's render distance
+    pub fn mesh_from_ref(&mut self, ref_point: &ReferencePoint, config: &MeshConfig) -> Mesh {
+        let (min, max) = self.ref_region(ref_point);
+
+        let coords: Vec<(i32, i32)> = self.chunks_in_region(min, max).map(|(coord, _)| coord).collect();
+
+        let mut union: Mesh = Mesh { vertices: vec![], indices: vec![] };
         
-        let min_chunk_x = ((ref_point.position.1 - ref_point.render_dist)/scale).floor() as i32;
-        let max_chunk_x = ((ref_point.position.1 + ref_point.render_dist)/scale).ceil() as i32;
-        let min_chunk_y = ((ref_point.position.0 - ref_point.render_dist)/scale).floor() as i32;
-        let max_chunk_y = ((ref_point.position.0 + ref_point.render_dist)/scale).ceil() as i32;
+        for coord in coords {
+            if let Some(mesh) = self.chunk_mesh(coord, config) {
+                union.union(mesh);
+            }
+        }
+
+        union
+    }
 
-        println!("min: {0}, {1}", min_chunk_x, min_chunk_y);
-        println!("max: {0}, {1}", max_chunk_x, max_chunk_y);
+    // Samples from min to max inclusive, one pixel each, min at the first pixel. Chunks paged
+    // out are read from storage without loading them, chunks never stored show as missing
+    pub fn region_to_image(&self, min: (i32, i32), max: (i32, i32), options: &ExportOptions) -> image::RgbImage {
+        let width = (max.0 - min.0 + 1).max(0) as usize;
+        let height = (max.1 - min.1 + 1).max(0) as usize;
+        let size = self.chunk_size as i32;
 
-        for chunk_x in min_chunk_x..=max_chunk_x {
-            for chunk_y in min_chunk_y..=max_chunk_y {
-                if let Some(chunk) = self.get_chunk((chunk_x, chunk_y)) {
-                    let mut edged_chunk = Chunk::new(33);
-                    edged_chunk.add_data(&chunk.data, 0, 0);
-                    match self.get_chunk((chunk_x + 1, chunk_y)) {
-                        Some(v) => {edged_chunk.add_data(&v.get_edge(0), 32, 0);},
-                        _ => {}
-                    }
-                    match self.get_chunk((chunk_x, chunk_y + 1)) {
-                        Some(v) => {edged_chunk.add_data(&v.get_edge(1), 0, 32);},
-                        _ => {}
+        let mut stored = HashMap::new();
+        if let Some(storage) = &self.storage {
+            let (min_chunk, max_chunk) = (self.sample_to_chunk(min.0, min.1), self.sample_to_chunk(max.0, max.1));
+            for chunk_y in min_chunk.1..=max_chunk.1 {
+                for chunk_x in min_chunk.0..=max_chunk.0 {
+                    let coord = (chunk_x, chunk_y);
+                    if self.chunks.contains_key(&coord) {
+                        continue;
                     }
-                    match self.get_chunk((chunk_x + 1, chunk_y + 1)) {
-                        Some(v) => {edged_chunk.add_data(&v.get_edge(2), 32, 32);},
-                        _ => {}
+
+                    match storage.load_chunk(coord) {
+                        Ok(Some(chunk)) => { stored.insert(coord, chunk); },
+                        Ok(None) => {},
+                        Err(error) => log::error!("couldn't read chunk {:?} for export: {}", coord, error),
                     }
-                    let mut chunk_mesh = SquareSet::chunk_to_sets(&edged_chunk).to_mesh();
-                    chunk_mesh.translate([chunk_y as f32, chunk_x as f32, 0.0]);
-                    meshes.push(chunk_mesh);
                 }
             }
         }
 
-        let mut union: Mesh = Mesh { vertices: vec![], indices: vec![] };
-        
-        for mesh in meshes {
-            union.union(&mesh);
+        image_io::render_image(
+            width,
+            height,
+            options,
+            |x, y| {
+                let (coord, (local_x, local_y)) = self.sample_to_local(min.0 + x as i32, min.1 + y as i32);
+                self.chunk(coord).or_else(|| stored.get(&coord)).map(|chunk| chunk.get(local_x, local_y))
+            },
+            |x, y| (min.0 + x as i32).rem_euclid(size) == 0 || (min.1 + y as i32).rem_euclid(size) == 0,
+        )
+    }
+
+    pub fn save_region_png(&self, path: impl AsRef<std::path::Path>, min: (i32, i32), max: (i32, i32), options: &ExportOptions) -> image::ImageResult<()> {
+        self.region_to_image(min, max, options).save_with_format(path, image::ImageFormat::Png)
+    }
+
+    // Writes the seed, chunk size and every loaded chunk, along with chunks paged out to
+    // storage. Generators aren't saved
+    pub fn write_to(&self, writer: &mut impl Write) -> Result<(), WorldError> {
+        let mut coords: Vec<(i32, i32)> = self.chunks.keys().copied().collect();
+        if let Some(storage) = &self.storage {
+            // Loaded chunks are newer than their stored copies
+            coords.extend(storage.stored_chunks()?.into_iter().filter(|coord| !self.chunks.contains_key(coord)));
         }
-        println!("idk{0}, {1}", -(ref_point.position.0 as f32)/32.0, -(ref_point.position.1 as f32)/32.0);
-        union.translate([-(ref_point.position.0 as f32)/scale, -(ref_point.position.1 as f32)/scale, 0.0]);
+        // Sorted so the same plane always writes the same bytes
+        coords.sort();
 
-        union
+        world::write_header(writer, &Header {
+            seed: self.seed,
+            chunk_size: self.chunk_size,
+            chunk_count: coords.len(),
+        })?;
+
+        for coord in coords {
+            match (self.chunks.get(&coord), &self.storage) {
+                (Some(chunk), _) => world::write_chunk(writer, coord, chunk)?,
+                (None, Some(storage)) => {
+                    let chunk = storage.load_chunk(coord)?
+                        .ok_or_else(|| WorldError::Corrupt(format!("stored chunk {:?} disappeared while saving", coord)))?;
+                    world::write_chunk(writer, coord, &chunk)?;
+                },
+                (None, None) => unreachable!("only stored chunks can be missing"),
+            }
+        }
+
+        Ok(())
+    }
+
+    // A plane with the saved seed and chunks, generating new chunks solid until given a generator
+    pub fn read_from(reader: &mut impl Read) -> Result<Self, WorldError> {
+        let header = world::read_header(reader)?;
+        let mut plane = Self::with_chunk_size(header.chunk_size).with_seed(header.seed);
+
+        for _ in 0..header.chunk_count {
+            let (coord, chunk) = world::read_chunk(reader, header.chunk_size)?;
+            if plane.set_chunk(coord, chunk).is_some() {
+                return Err(WorldError::Corrupt(format!("chunk {:?} is stored twice", coord)));
+            }
+        }
+
+        Ok(plane)
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), WorldError> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        self.write_to(&mut writer)?;
+        writer.flush()?;
+
+        Ok(())
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, WorldError> {
+        Self::read_from(&mut BufReader::new(File::open(path)?))
     }
 
     pub fn clone_area(&mut self, chunk: &Chunk, start_x: i32, start_y: i32, end_x: i32, end_y: i32) {
@@ -106,48 +654,57 @@ impl Plane {
         }
     }
 
-    pub fn paint_antialiased_filled_circle(&mut self, x: f32, y: f32, radius: f32) -> &mut Self {
-        let min_chunk_x = ((x - radius).floor() as i32)/32;
-        let max_chunk_x = ((x + radius).ceil() as i32)/32;
-        let min_chunk_y = ((y - radius).floor() as i32)/32;
-        let max_chunk_y = ((y + radius).ceil() as i32)/32;
-        
+    // Blends the brush, given in world positions, into every chunk its bounds touch
+    pub fn paint<B: Brush + ?Sized>(&mut self, brush: &B, mode: BlendMode, falloff: f32) -> &mut Self {
+        let (min, max) = brush.bounds();
+        let (min_chunk_x, min_chunk_y) = self.world_to_chunk(min.0, min.1);
+        let (max_chunk_x, max_chunk_y) = self.world_to_chunk(max.0, max.1);
+
         for chunk_x in min_chunk_x..=max_chunk_x {
             for chunk_y in min_chunk_y..=max_chunk_y {
-                let mut chunk = self.get_or_gen_chunk((chunk_x, chunk_y));
-                let local_x = x - chunk_x as f32 * 32.0;
-                let local_y = y - chunk_y as f32 * 32.0;
-                chunk.paint_antialiased_filled_circle(local_x, local_y, radius);
-                self.set_chunk((chunk_x, chunk_y), chunk);
+                // Chunks in the bounds the brush doesn't reach aren't generated or marked changed
+                let origin = self.chunk_to_world((chunk_x, chunk_y));
+                if brush::covered_samples(brush, origin, self.chunk_size).next().is_none() {
+                    continue;
+                }
+
+                match self.chunk_or_generate_mut((chunk_x, chunk_y)) {
+                    Ok(chunk) => { chunk.paint_offset(brush, origin, mode, falloff); },
+                    // Chunks that can't be read are left unpainted rather than replaced
+                    Err(error) => log::error!("couldn't page in chunk {:?} to paint it: {}", (chunk_x, chunk_y), error),
+                }
             }
         }
 
         self
     }
 
+    pub fn paint_circle(&mut self, x: f32, y: f32, radius: f32, mode: BlendMode, falloff: f32) -> &mut Self {
+        self.paint(&Circle { centre: (x, y), radius }, mode, falloff)
+    }
+
+    pub fn paint_antialiased_filled_circle(&mut self, x: f32, y: f32, radius: f32) -> &mut Self {
+        self.paint_circle(x, y, radius, BlendMode::Carve, 1.0)
+    }
+
+    pub fn fill_antialiased_filled_circle(&mut self, x: f32, y: f32, radius: f32) -> &mut Self {
+        self.paint_circle(x, y, radius, BlendMode::Fill, 1.0)
+    }
+
+}
+
+impl Default for Plane {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
-impl Meshable for Plane where {
-    fn to_mesh(&self) -> Mesh {
+impl Meshable for Plane {
+    fn to_mesh(&self, config: &MeshConfig) -> Mesh {
         let mut meshes: Vec<Mesh> = vec![];
         
-        for ((x, y), chunk) in self.chunks.iter() {
-            let mut edged_chunk = Chunk::new(33);
-            edged_chunk.add_data(&chunk.data, 0, 0);
-            match self.get_chunk((*x + 1, *y)) {
-                Some(v) => {edged_chunk.add_data(&v.get_edge(0), 32, 0);},
-                _ => {}
-            }
-            match self.get_chunk((*x, *y + 1)) {
-                Some(v) => {edged_chunk.add_data(&v.get_edge(1), 0, 32);},
-                _ => {}
-            }
-            match self.get_chunk((*x + 1, *y + 1)) {
-                Some(v) => {edged_chunk.add_data(&v.get_edge(2), 32, 32);},
-                _ => {}
-            }
-            let mut chunk_mesh = SquareSet::chunk_to_sets(&edged_chunk).to_mesh();
-            chunk_mesh.translate([*y as f32, *x as f32, 0.0]);
+        for (x, y) in self.chunks.keys() {
+            let Some(chunk_mesh) = self.build_chunk_mesh((*x, *y), config) else { continue };
             meshes.push(chunk_mesh);
         }
 
@@ -0,0 +1,324 @@
+use std::fs::{ self, File, OpenOptions };
+use std::io::{ Read, Seek, SeekFrom, Write };
+use std::path::{ Path, PathBuf };
+
+use crate::chunk::Chunk;
+use crate::world::{ self, WorldError };
+
+// Region files start with the magic, a version, the chunk size and the region size, then a
+// table with the offset (u64) and length (u32) of every chunk in the region, zero when it
+// hasn't been stored. Chunks are encoded as in world files and rewritten in place when they fit.
+// Chunks that outgrow their space move to the end, and once the space left behind is more than
+// the chunks still use the file is rewritten without it
+pub const REGION_MAGIC: [u8; 4] = *b"MSQR";
+pub const REGION_VERSION: u32 = 1;
+
+const HEADER_LENGTH: u64 = 16;
+const ENTRY_LENGTH: u64 = 12;
+
+// World header with no chunks followed by the region size (u32), recording what the regions
+// were written with
+const META_NAME: &str = "meta.msqw";
+const DEFAULT_REGION_SIZE: i32 = 32;
+
+// Pages chunks in and out of a directory of region files, each holding a square of chunks
+#[derive(Clone, Debug)]
+pub struct RegionStore {
+    directory: PathBuf,
+    seed: i32,
+    chunk_size: usize,
+    // Chunks along each side of a region
+    region_size: i32,
+}
+
+impl RegionStore {
+    pub fn new(directory: impl AsRef<Path>, seed: i32, chunk_size: usize) -> Result<Self, WorldError> {
+        Self::with_region_size(directory, seed, chunk_size, DEFAULT_REGION_SIZE)
+    }
+
+    // Creates the directory, or checks an existing one was written for the same seed, chunk
+    // size and region size
+    pub fn with_region_size(directory: impl AsRef<Path>, seed: i32, chunk_size: usize, region_size: i32) -> Result<Self, WorldError> {
+        assert!(region_size > 0, "regions must hold at least one chunk");
+        fs::create_dir_all(directory.as_ref())?;
+
+        let store = Self {
+            directory: directory.as_ref().to_path_buf(),
+            seed,
+            chunk_size,
+            region_size,
+        };
+
+        match Self::open(&directory) {
+            Ok(existing) => {
+                if (existing.seed, existing.chunk_size, existing.region_size) != (seed, chunk_size, region_size) {
+                    return Err(WorldError::Corrupt(format!(
+                        "regions have seed {}, chunk size {} and region size {}, expected {}, {} and {}",
+                        existing.seed, existing.chunk_size, existing.region_size, seed, chunk_size, region_size
+                    )));
+                }
+            },
+            Err(WorldError::Io(error)) if error.kind() == std::io::ErrorKind::NotFound => store.write_meta()?,
+            Err(error) => return Err(error),
+        }
+
+        Ok(store)
+    }
+
+    // Opens a directory written by RegionStore::new or with_region_size with the seed, chunk
+    // size and region size it was written with, failing with a not found error when there's
+    // nothing to open
+    pub fn open(directory: impl AsRef<Path>) -> Result<Self, WorldError> {
+        let mut file = File::open(directory.as_ref().join(META_NAME))?;
+        let header = world::read_header(&mut file)?;
+
+        let mut region_size = [0; 4];
+        file.read_exact(&mut region_size)?;
+        let region_size = u32::from_le_bytes(region_size);
+        if region_size == 0 || region_size > i32::MAX as u32 {
+            return Err(WorldError::Corrupt(format!("region size {} is out of range", region_size)));
+        }
+
+        Ok(Self {
+            directory: directory.as_ref().to_path_buf(),
+            seed: header.seed,
+            chunk_size: header.chunk_size,
+            region_size: region_size as i32,
+        })
+    }
+
+    fn write_meta(&self) -> Result<(), WorldError> {
+        let mut meta = vec![];
+        world::write_header(&mut meta, &world::Header { seed: self.seed, chunk_size: self.chunk_size, chunk_count: 0 })?;
+        meta.extend((self.region_size as u32).to_le_bytes());
+        fs::write(self.directory.join(META_NAME), meta)?;
+
+        Ok(())
+    }
+
+    pub fn seed(&self) -> i32 {
+        self.seed
+    }
+
+    pub fn chunk_size(&self) -> usize {
+        self.chunk_size
+    }
+
+    pub fn region_size(&self) -> i32 {
+        self.region_size
+    }
+
+    // Region holding the chunk, rounding towards negative infinity
+    pub fn chunk_to_region(&self, coord: (i32, i32)) -> (i32, i32) {
+        (coord.0.div_euclid(self.region_size), coord.1.div_euclid(self.region_size))
+    }
+
+    fn path(&self, region: (i32, i32)) -> PathBuf {
+        self.directory.join(format!("r.{}.{}.msqr", region.0, region.1))
+    }
+
+    fn entry_position(&self, coord: (i32, i32)) -> u64 {
+        let local = (coord.0.rem_euclid(self.region_size), coord.1.rem_euclid(self.region_size));
+        HEADER_LENGTH + (local.1 * self.region_size + local.0) as u64 * ENTRY_LENGTH
+    }
+
+    // Where the table ends and chunk data starts
+    fn data_start(&self) -> u64 {
+        HEADER_LENGTH + (self.region_size * self.region_size) as u64 * ENTRY_LENGTH
+    }
+
+    // Header and table of a region with nothing stored
+    fn empty_region(&self) -> Vec<u8> {
+        let mut header = Vec::with_capacity(self.data_start() as usize);
+        header.extend(REGION_MAGIC);
+        header.extend(REGION_VERSION.to_le_bytes());
+        header.extend((self.chunk_size as u32).to_le_bytes());
+        header.extend((self.region_size as u32).to_le_bytes());
+        header.resize(self.data_start() as usize, 0);
+
+        header
+    }
+
+    // Checks an existing region file was written for regions and chunks of this size
+    fn check_header(&self, file: &mut File) -> Result<(), WorldError> {
+        let mut header = [0; HEADER_LENGTH as usize];
+        file.seek(SeekFrom::Start(0))?;
+        file.read_exact(&mut header)?;
+
+        let magic: [u8; 4] = header[0..4].try_into().unwrap();
+        if magic != REGION_MAGIC {
+            return Err(WorldError::BadMagic(magic));
+        }
+
+        let field = |i: usize| u32::from_le_bytes(header[i..i + 4].try_into().unwrap());
+        if field(4) != REGION_VERSION {
+            return Err(WorldError::UnsupportedVersion(field(4)));
+        }
+        if field(8) as usize != self.chunk_size || field(12) as i32 != self.region_size {
+            return Err(WorldError::Corrupt(format!(
+                "region has chunk size {} and region size {}, expected {} and {}",
+                field(8), field(12), self.chunk_size, self.region_size
+            )));
+        }
+
+        Ok(())
+    }
+
+    // Offset and length of every chunk in the region, row by row
+    fn read_table(&self, file: &mut File) -> Result<Vec<(u64, u32)>, WorldError> {
+        let mut table = vec![0; (self.data_start() - HEADER_LENGTH) as usize];
+        file.seek(SeekFrom::Start(HEADER_LENGTH))?;
+        file.read_exact(&mut table)?;
+
+        Ok(table.chunks_exact(ENTRY_LENGTH as usize)
+            .map(|entry| (
+                u64::from_le_bytes(entry[0..8].try_into().unwrap()),
+                u32::from_le_bytes(entry[8..12].try_into().unwrap()),
+            ))
+            .collect())
+    }
+
+    fn read_entry(&self, file: &mut File, coord: (i32, i32)) -> Result<(u64, u32), WorldError> {
+        let mut entry = [0; ENTRY_LENGTH as usize];
+        file.seek(SeekFrom::Start(self.entry_position(coord)))?;
+        file.read_exact(&mut entry)?;
+
+        Ok((
+            u64::from_le_bytes(entry[0..8].try_into().unwrap()),
+            u32::from_le_bytes(entry[8..12].try_into().unwrap()),
+        ))
+    }
+
+    // The stored chunk, or None if it was never stored
+    pub fn load_chunk(&self, coord: (i32, i32)) -> Result<Option<Chunk>, WorldError> {
+        let mut file = match File::open(self.path(self.chunk_to_region(coord))) {
+            Ok(file) => file,
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(error) => return Err(error.into()),
+        };
+        self.check_header(&mut file)?;
+
+        let (offset, length) = self.read_entry(&mut file, coord)?;
+        if offset == 0 {
+            return Ok(None);
+        }
+
+        let mut bytes = vec![0; length as usize];
+        file.seek(SeekFrom::Start(offset))?;
+        file.read_exact(&mut bytes)?;
+
+        let (stored_coord, chunk) = world::read_chunk(&mut bytes.as_slice(), self.chunk_size)?;
+        if stored_coord != coord {
+            return Err(WorldError::Corrupt(format!("chunk {:?} is stored where {:?} should be", stored_coord, coord)));
+        }
+
+        Ok(Some(chunk))
+    }
+
+    // Every chunk stored in the directory's region files, in no particular order
+    pub fn stored_chunks(&self) -> Result<Vec<(i32, i32)>, WorldError> {
+        let mut coords = vec![];
+
+        for entry in fs::read_dir(&self.directory)? {
+            let path = entry?.path();
+            let Some(region) = path.file_name().and_then(|name| name.to_str()).and_then(parse_region_name) else { continue };
+
+            let mut file = File::open(&path)?;
+            self.check_header(&mut file)?;
+
+            for (i, (offset, _)) in self.read_table(&mut file)?.into_iter().enumerate() {
+                if offset != 0 {
+                    let local = (i as i32 % self.region_size, i as i32 / self.region_size);
+                    coords.push((region.0 * self.region_size + local.0, region.1 * self.region_size + local.1));
+                }
+            }
+        }
+
+        Ok(coords)
+    }
+
+    pub fn save_chunk(&self, coord: (i32, i32), chunk: &Chunk) -> Result<(), WorldError> {
+        assert_eq!(chunk.size(), self.chunk_size, "chunk size does not match the region store");
+
+        let region = self.chunk_to_region(coord);
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(self.path(region))?;
+
+        if file.metadata()?.len() == 0 {
+            file.write_all(&self.empty_region())?;
+        }
+        self.check_header(&mut file)?;
+
+        let mut bytes = vec![];
+        world::write_chunk(&mut bytes, coord, chunk)?;
+
+        // Reuse the old space when the chunk still fits, otherwise append
+        let (old_offset, old_length) = self.read_entry(&mut file, coord)?;
+        let appended = old_offset == 0 || bytes.len() > old_length as usize;
+        let offset = if appended {
+            file.seek(SeekFrom::End(0))?
+        } else {
+            file.seek(SeekFrom::Start(old_offset))?
+        };
+        file.write_all(&bytes)?;
+
+        let mut entry = Vec::with_capacity(ENTRY_LENGTH as usize);
+        entry.extend(offset.to_le_bytes());
+        entry.extend((bytes.len() as u32).to_le_bytes());
+        file.seek(SeekFrom::Start(self.entry_position(coord)))?;
+        file.write_all(&entry)?;
+
+        if appended {
+            let used: u64 = self.read_table(&mut file)?.iter().map(|&(_, length)| length as u64).sum();
+            let end = file.seek(SeekFrom::End(0))?;
+            if end - self.data_start() > 2 * used {
+                drop(file);
+                self.compact(region)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    // Rewrites the region with its chunks packed one after another behind the table. The
+    // new file replaces the old one only once it is complete
+    fn compact(&self, region: (i32, i32)) -> Result<(), WorldError> {
+        let path = self.path(region);
+        let mut file = File::open(&path)?;
+        self.check_header(&mut file)?;
+
+        let mut compacted = self.empty_region();
+        for (i, (offset, length)) in self.read_table(&mut file)?.into_iter().enumerate() {
+            if offset == 0 {
+                continue;
+            }
+
+            let start = compacted.len();
+            let entry = HEADER_LENGTH as usize + i * ENTRY_LENGTH as usize;
+            compacted[entry..entry + 8].copy_from_slice(&(start as u64).to_le_bytes());
+            compacted[entry + 8..entry + 12].copy_from_slice(&length.to_le_bytes());
+
+            compacted.resize(start + length as usize, 0);
+            file.seek(SeekFrom::Start(offset))?;
+            file.read_exact(&mut compacted[start..])?;
+        }
+        drop(file);
+
+        let temporary = path.with_extension("msqr.tmp");
+        fs::write(&temporary, &compacted)?;
+        fs::rename(&temporary, &path)?;
+
+        Ok(())
+    }
+}
+
+// The region a file named by RegionStore::path holds
+fn parse_region_name(name: &str) -> Option<(i32, i32)> {
+    let (x, y) = name.strip_prefix("r.")?.strip_suffix(".msqr")?.split_once('.')?;
+
+    Some((x.parse().ok()?, y.parse().ok()?))
+}
@@ -0,0 +1,203 @@
+use crate::brush::{ self, Brush };
+use crate::chunk::Chunk;
+use crate::contour::Contour;
+use crate::mesh::{ Interpolation, Mesh, MeshConfig, Meshable };
+use crate::square_march::SquareSet;
+
+// How a second distance field combines with an existing one
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum CsgOp {
+    Union,
+    Intersection,
+    // Removes the second shape from the first
+    Subtraction,
+    // The smooth variants round the seam over the given width in cells
+    SmoothUnion(f32),
+    SmoothIntersection(f32),
+    SmoothSubtraction(f32),
+}
+
+impl CsgOp {
+    pub fn apply(self, a: f32, b: f32) -> f32 {
+        match self {
+            CsgOp::Union => a.min(b),
+            CsgOp::Intersection => a.max(b),
+            CsgOp::Subtraction => a.max(-b),
+            // The smooth minimum of two distances is the negated smooth maximum of their negations
+            CsgOp::SmoothUnion(width) => -brush::smooth_max(-a, -b, width),
+            CsgOp::SmoothIntersection(width) => brush::smooth_max(a, b, width),
+            CsgOp::SmoothSubtraction(width) => brush::smooth_max(a, -b, width),
+        }
+    }
+}
+
+// Signed distance in cells to the nearest edge, negative inside, clamped to +-max_distance.
+// Samples are stored row by row like Chunk, so sample (x, y) is at data[y * size + x]
+#[repr(C)]
+#[derive(Clone)]
+pub struct SdfChunk {
+    data: Vec<f32>,
+    size: usize,
+    max_distance: f32
+}
+
+impl SdfChunk {
+    // Entirely outside
+    pub fn new(size: usize, max_distance: f32) -> Self {
+        Self::filled(size, max_distance, max_distance)
+    }
+
+    // Entirely inside
+    pub fn solid(size: usize, max_distance: f32) -> Self {
+        Self::filled(size, -max_distance, max_distance)
+    }
+
+    pub fn filled(size: usize, distance: f32, max_distance: f32) -> Self {
+        assert!(size > 0, "sdf chunk size must be at least one sample");
+        assert!(max_distance > 0.0, "max distance must be positive");
+        Self {
+            data: vec![distance.clamp(-max_distance, max_distance); size * size],
+            size,
+            max_distance
+        }
+    }
+
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    pub fn max_distance(&self) -> f32 {
+        self.max_distance
+    }
+
+    fn index(&self, x: usize, y: usize) -> usize {
+        debug_assert!(x < self.size && y < self.size, "sample ({x}, {y}) outside chunk of size {}", self.size);
+        y * self.size + x
+    }
+
+    pub fn get(&self, x: usize, y: usize) -> f32 {
+        self.data[self.index(x, y)]
+    }
+
+    pub fn set(&mut self, x: usize, y: usize, distance: f32) -> &mut Self {
+        let index = self.index(x, y);
+        self.data[index] = distance.clamp(-self.max_distance, self.max_distance);
+
+        self
+    }
+
+    pub fn as_slice(&self) -> &[f32] {
+        &self.data
+    }
+
+    // Combines other into this sample by sample, both must be the same size
+    pub fn combine(&mut self, other: &SdfChunk, op: CsgOp) -> &mut Self {
+        assert_eq!(self.size, other.size, "sdf chunks must be the same size to combine");
+
+        for (distance, &other_distance) in self.data.iter_mut().zip(&other.data) {
+            *distance = op.apply(*distance, other_distance).clamp(-self.max_distance, self.max_distance);
+        }
+
+        self
+    }
+
+    pub fn union(&mut self, other: &SdfChunk) -> &mut Self {
+        self.combine(other, CsgOp::Union)
+    }
+
+    pub fn intersection(&mut self, other: &SdfChunk) -> &mut Self {
+        self.combine(other, CsgOp::Intersection)
+    }
+
+    pub fn subtraction(&mut self, other: &SdfChunk) -> &mut Self {
+        self.combine(other, CsgOp::Subtraction)
+    }
+
+    pub fn paint<B: Brush + ?Sized>(&mut self, brush: &B, op: CsgOp) -> &mut Self {
+        self.paint_offset(brush, (0.0, 0.0), op)
+    }
+
+    // Combines the brush's distance with every sample, as if sample (0, 0) were at origin.
+    // Intersections change samples far from the brush, so nothing is skipped by its bounds
+    pub fn paint_offset<B: Brush + ?Sized>(&mut self, brush: &B, origin: (f32, f32), op: CsgOp) -> &mut Self {
+        for (i, distance) in self.data.iter_mut().enumerate() {
+            let (x, y) = ((i % self.size) as f32, (i / self.size) as f32);
+            let brush_distance = brush.distance(origin.0 + x, origin.1 + y);
+            *distance = op.apply(*distance, brush_distance).clamp(-self.max_distance, self.max_distance);
+        }
+
+        self
+    }
+
+    // Distances from each sample to the density's contour segments. Contours of a lone chunk
+    // stop at its edges, so samples near an edge can miss closer contours just outside it
+    pub fn from_density(chunk: &Chunk, config: &MeshConfig, max_distance: f32) -> Self {
+        let segments: Vec<([f32; 2], [f32; 2])> = chunk.contours(config)
+            .iter()
+            .flat_map(contour_segments)
+            .collect();
+
+        let mut sdf = Self::new(chunk.size(), max_distance);
+
+        for (i, value) in chunk.as_slice().iter().enumerate() {
+            let point = [(i % chunk.size()) as f32, (i / chunk.size()) as f32];
+
+            let distance = segments.iter()
+                .map(|&(from, to)| distance_to_segment(point, from, to))
+                .fold(max_distance, f32::min);
+
+            sdf.data[i] = if *value > config.iso { -distance } else { distance };
+        }
+
+        sdf
+    }
+
+    // Density that crosses the iso value exactly where the distance is zero. The whole
+    // distance range is scaled to fit in 0..=1, so density stays linear in the distance,
+    // which needs room both sides of the iso value
+    pub fn to_density(&self, config: &MeshConfig) -> Chunk {
+        assert!(config.iso > 0.0 && config.iso < 1.0, "iso {} must be between 0 and 1 to convert distances to density", config.iso);
+        let scale = config.iso.min(1.0 - config.iso) / self.max_distance;
+        let data = self.data.iter()
+            .map(|distance| (config.iso - distance * scale).clamp(0.0, 1.0))
+            .collect();
+
+        Chunk::from_vec(self.size, data)
+    }
+
+    pub fn contours(&self, config: &MeshConfig) -> Vec<Contour> {
+        let config = linear(config);
+        self.to_density(&config).contours(&config)
+    }
+}
+
+impl Meshable for SdfChunk {
+    fn to_mesh(&self, config: &MeshConfig) -> Mesh {
+        let config = linear(config);
+        SquareSet::chunk_to_sets(&self.to_density(&config)).to_mesh(&config)
+    }
+}
+
+// Density from a distance field is linear across each cell, so linear interpolation
+// puts vertices exactly on the zero distance
+fn linear(config: &MeshConfig) -> MeshConfig {
+    MeshConfig { interpolation: Interpolation::Linear, ..*config }
+}
+
+fn contour_segments(contour: &Contour) -> Vec<([f32; 2], [f32; 2])> {
+    let mut segments: Vec<([f32; 2], [f32; 2])> = contour.points.windows(2)
+        .map(|pair| (pair[0], pair[1]))
+        .collect();
+
+    if contour.closed && contour.points.len() > 1 {
+        segments.push((contour.points[contour.points.len() - 1], contour.points[0]));
+    }
+
+    segments
+}
+
+fn distance_to_segment(point: [f32; 2], from: [f32; 2], to: [f32; 2]) -> f32 {
+    let closest = brush::closest_on_segment((point[0], point[1]), (from[0], from[1]), (to[0], to[1]));
+
+    ((point[0] - closest.0).powi(2) + (point[1] - closest.1).powi(2)).sqrt()
+}
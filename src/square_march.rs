@@ -1,4 +1,7 @@
-use crate::{ Chunk, Vertex, Mesh, Meshable, HashMap };
+use std::collections::HashMap;
+
+use crate::chunk::Chunk;
+use crate::mesh::{ Interpolation, Mesh, MeshConfig, Meshable, Vertex };
 
 #[repr(C)]
 #[derive(Copy, Clone)]
@@ -10,182 +13,151 @@ pub struct SquareSet {
 
 impl SquareSet {
     pub fn chunk_to_sets(chunk: &Chunk) -> Vec<Self> {
-        /*
-        [
-            abcd
-            1234
-            ijlk
-            ..
-        ]
-            .windows(2).map(x0.windows(2).zip(x1.windows(2)))
-        [
-            [
-                [ab][bc][cd]
-                [12][23][34]
-            ]
-            [
-                [12][23][34]
-                [ij][jl][lk]
-            ]
-            ..
-        ]
-            .flat_map.map
-        [   set  set  set  set  set
-            [ab  [bc  [cd  [12  ..
-        ]    12]  23]  34]  ij] 
-        */
-
-        chunk.data.windows(2)
-            .map(|x| 
-                 x[0].windows(2).zip(x[1].windows(2))
-            ).flat_map(|x| {                // [[un_map][in_map]]      -> [map, map] 
-
-                x.map(|y| Self {            // tuples                     set
-                    a: y.0[0], b: y.0[1],   // ((f32, f32),(f32, f32)) -> ab
-                    d: y.1[0], c: y.1[1]    //                            dc
+        // One set per square of four samples, row by row:
+        //
+        // (x, y)     (x+1, y)        a b
+        // (x, y+1)   (x+1, y+1)  ->  d c
+
+        chunk.rows()
+            .zip(chunk.rows().skip(1))
+            .flat_map(|(row, next_row)| {
+                row.windows(2).zip(next_row.windows(2)).map(|(top, bottom)| Self {
+                    a: top[0],    b: top[1],
+                    d: bottom[0], c: bottom[1],
                 })
-        }).collect()
+            })
+            .collect()
     }
 
-    fn rotate(&mut self, _counter_clock: bool) -> &mut Self {
-        // Rotations:
-        // 1)ab 2)da 3)cd 4)bc
-        //   dc   cb   ba   ad
-        
-        let temp = self.a;
-        self.a = self.d;
-        self.d = self.c;
-        self.c = self.b;
-        self.b = temp;
-
-        //let temp: f32 = self.a;
-        //self.a = self.b;
-        //self.b = self.c;
-        //self.c = self.d;
-        //self.d = temp;
-        
-        self
+    pub fn case_index(&self, iso: f32) -> usize {
+        // Bits: a=1, b=2, c=4, d=8
+        (self.a > iso) as usize
+            | ((self.b > iso) as usize) << 1
+            | ((self.c > iso) as usize) << 2
+            | ((self.d > iso) as usize) << 3
+    }
+
+    fn polygons(&self, config: &MeshConfig) -> &'static [&'static [Point]] {
+        let case = self.case_index(config.iso);
+
+        match case {
+            5 | 10 if !config.saddle.connects(self, config.iso) => SEPARATED_SADDLES[case / 5 - 1],
+            _ => CASES[case],
+        }
+    }
+
+    // Pairs of sides joined by the contour, ordered so the inside is on the left
+    pub(crate) fn contour_sides(&self, config: &MeshConfig) -> Vec<(Point, Point)> {
+        let mut sides = vec![];
+
+        for polygon in self.polygons(config) {
+            for (i, from) in polygon.iter().enumerate() {
+                let to = polygon[(i + 1) % polygon.len()];
+                if from.is_side() && to.is_side() {
+                    sides.push((*from, to));
+                }
+            }
+        }
+
+        sides
     }
 }
 
-impl Meshable for SquareSet where {
-    fn to_mesh(&self) -> Mesh {
-        let cutoff = 0.2;
-        
-        let mut rotation = self.clone();
-        for i in 0..=3 {
-            let output: Option<Mesh> = match rotation {
-                _ if check_pattern_1(&rotation, cutoff) => Some(pattern_1(rotation)),
-                _ if check_pattern_2(&rotation, cutoff) => Some(pattern_2(rotation)),
-
-                _ if check_pattern_3(&rotation, cutoff) => Some(pattern_3(rotation)),
-                _ if check_pattern_4(&rotation, cutoff) => Some(pattern_4(rotation)),
-                _ if check_pattern_5(&rotation, cutoff) => Some(pattern_5(rotation)),
-                _ => None
-            };
-        
-            match output {
-                Some(x) => {
-                    let mut rotated_mesh: Mesh = x.clone();
-                    
-                    for _ in 0..i {
-                        rotated_mesh.rotate90(false);
-                    }
-                    
-                    return rotated_mesh
-                },
-                None => ()
+
+// How the two diagonal corners of a saddle (case 5: a+c, case 10: b+d) are joined
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum SaddleRule {
+    // The corners above the cutoff always form one connected region
+    #[default]
+    Connect,
+    // The corners above the cutoff are always split into two triangles
+    Separate,
+    // Connected when the average of all four corners is above the cutoff
+    CenterAverage,
+    // Connected when the bilinear saddle point is above the cutoff
+    AsymptoticDecider,
+}
+
+impl SaddleRule {
+    fn connects(&self, set: &SquareSet, cutoff: f32) -> bool {
+        let average = (set.a + set.b + set.c + set.d) / 4.0;
+
+        match self {
+            SaddleRule::Connect => true,
+            SaddleRule::Separate => false,
+            SaddleRule::CenterAverage => average > cutoff,
+            SaddleRule::AsymptoticDecider => {
+                // Only saddles are decided, where one diagonal is above the cutoff and the
+                // other isn't, so the denominator can't be zero
+                let denominator = set.a + set.c - set.b - set.d;
+                debug_assert!(denominator != 0.0, "asymptotic decider called on a set that isn't a saddle");
+
+                (set.a * set.c - set.b * set.d) / denominator > cutoff
             }
-            
-            rotation.rotate(false);
         }
-        
-        Mesh { vertices: vec![], indices: vec![] }
     }
 }
 
-impl Meshable for Vec<SquareSet> where {
-    fn to_mesh(&self) -> Mesh {
-        let size = /*(self.len() as f32).sqrt()*/32 as i32;
-        let mut meshes: Vec<Mesh> = vec![];
-        
-        let mut i = 0;
-        for set in self {
+impl Meshable for SquareSet {
+    fn to_mesh(&self, config: &MeshConfig) -> Mesh {
+        let mut mesh = Mesh { vertices: vec![], indices: vec![] };
+
+        for polygon in self.polygons(config) {
+            let offset = mesh.vertices.len() as u32;
+
+            for point in polygon.iter() {
+                mesh.vertices.push(Vertex { position: point.position(self, config), colour: [0.0, 0.0, 0.0] });
+            }
+
+            for i in 1..polygon.len() as u32 - 1 {
+                mesh.indices.extend([offset, offset + i, offset + i + 1]);
+            }
+        }
+
+        mesh
+    }
+}
+
+impl Meshable for Vec<SquareSet> {
+    fn to_mesh(&self, config: &MeshConfig) -> Mesh {
+        // Sets come from a square chunk, row by row
+        let size = (self.len() as f32).sqrt().round().max(1.0) as usize;
+
+        // Sets sharing a corner or side get the same key, so their vertices
+        // weld without comparing positions
+        let mut welded: HashMap<GridKey, u32> = HashMap::with_capacity(self.len() * 2);
+        let mut vertices: Vec<Vertex> = vec![];
+        let mut indices: Vec<u32> = vec![];
+
+        for (i, set) in self.iter().enumerate() {
             let x = i%size;
             let y = i/size;
 
-            let mut set_mesh = set.to_mesh();
-            set_mesh.translate([x as f32, y as f32, 0.0]);
-            meshes.push(set_mesh);
-            
-            i += 1;
-        }
+            for polygon in set.polygons(config) {
+                let mut polygon_indices = [0; 6];
 
-        // new process
-        //
-        // check if any vertices are new, if so add them to the list
-        //
-        // then go through the vertices and map them to the list to get indices
-
-        let mut total_vertices: Vec<Vertex> = vec![];
-        let mut indices: Vec<i16> = vec![];
-
-        for mesh in meshes {
-            let mut index_map: Vec<i16> = vec![];
-            for vertex in &mesh.vertices {
-                match total_vertices.iter().position(|x| x.position[0] == vertex.position[0]
-                                                      && x.position[1] == vertex.position[1]
-                                                      && x.position[2] == vertex.position[2]) {
-                    Some (x) => index_map.push(x as i16),
-                    None => {
-                        index_map.push((total_vertices.len()) as i16);
-                        total_vertices.push(vertex.clone());
-                    }
+                for (point, index) in polygon.iter().zip(polygon_indices.iter_mut()) {
+                    *index = *welded.entry(point.grid_key(x as i32, y as i32)).or_insert_with(|| {
+                        let mut vertex = Vertex { position: point.position(set, config), colour: [0.0, 0.0, 0.0] };
+                        vertex.translate([x as f32, y as f32, 0.0]);
+                        vertices.push(vertex);
+
+                        (vertices.len() - 1) as u32
+                    });
                 }
 
-                //if total_vertices.iter().all(|x| !( x.position[0] == vertex.position[0] 
-                //                          && x.position[1] == vertex.position[1]
-                //                          && x.position[2] == vertex.position[2]
-                //)) {
-                //    total_vertices.push(*vertex);
-                //}
-            }
-            for index in &mesh.indices {
-                indices.push(index_map[*index as usize]);
+                for i in 1..polygon.len() - 1 {
+                    indices.extend([polygon_indices[0], polygon_indices[i], polygon_indices[i + 1]]);
+                }
             }
-            //indices.append();
         }
 
-            //for vertex in &mesh.vertices {
-            //    match total_vertices.iter().position(|x| x.position[0] == vertex.position[0]
-            //                                          && x.position[1] == vertex.position[1]
-            //                                          && x.position[2] == vertex.position[2]) {
-            //        Some (x) => indices.push(x as i16),
-            //        None =>  ()
-            //    }
-            //}
-            
-            //for vertex_index in 0..mesh.vertices.len() {
-            //    let vertex = mesh.vertices[vertex_index];
-            //    let pos = (
-            //        vertex.position[0],
-            //        vertex.position[1],
-            //        vertex.position[2],
-            //    );
-            //    match vertex_to_index.get(pos) {
-            //        Some(index) => mesh.indices.where
-            //    }
-            //    mesh.vertices.push();
-            //}
-            //total.union(&mesh);
-        
         let mut total: Mesh = Mesh {
-            vertices: total_vertices,
-            indices: indices
+            vertices,
+            indices
         };
         
         total.translate([0.5, 0.5, 0.0]);
-        total.scale(1.0/32.0);
 
         total
     }
@@ -195,212 +167,108 @@ fn smooth(i: f32) -> f32 {
     (std::f32::consts::E).powf(-1.0/i)
 }
 
-fn side_fn(p1: f32, p2: f32) -> f32 {
-            //1 => self.data[0].clone().iter().map(|&x| vec![x]).collect(),
-            //0 => vec![
-            //    vec![0.0],
-            //    vec![0.0],
-            //    vec![0.0],
-            //    vec![0.0],
-            //    vec![0.0],
-            //    vec![0.0],
-            //    vec![0.0],
-            //    vec![0.0],
-            //    vec![0.0],
-            //    vec![0.0],
-            //    vec![0.0],
-            //    vec![0.0],
-            //    vec![0.0],
-            //    vec![0.0],
-            //    vec![0.0],
-            //    vec![0.0],
-            //    vec![0.0],
-            //    vec![0.0],
-            //    vec![0.0],
-            //    vec![0.0],
-            //    vec![0.0],
-            //    vec![0.0],
-            //    vec![0.0],
-            //    vec![0.0],
-            //    vec![0.0],
-            //    vec![0.0],
-            //    vec![0.0],
-            //    vec![0.0],
-            //    vec![0.0],
-            //    vec![0.0],
-            //    vec![0.0],
-            //    vec![0.0],
-            //],
-            //1 => vec![ vec![0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0]],
-    let i = (p1 + p2) / 2.0;
-    let smooth_i = smooth(i);
-    smooth_i / (smooth_i + smooth(1.0 - i))
-    //0.5
-}
-
-// a b
-// c d
-//
-// Patterns:
-// 1)#. 2)## 3) #. 4)## 5)##
-//   ..   ..    .#   #.   ##
-
-fn check_pattern_1(set: &SquareSet, cutoff: f32) -> bool {
-    return set.a >  cutoff
-        && set.b <= cutoff
-        && set.c <= cutoff
-        && set.d <= cutoff
-}
-
-fn check_pattern_2(set: &SquareSet, cutoff: f32) -> bool {
-    return set.a >  cutoff
-        && set.b >  cutoff
-        && set.c <= cutoff
-        && set.d <= cutoff
-}
-
-fn check_pattern_3(set: &SquareSet, cutoff: f32) -> bool {
-    return set.a >  cutoff
-        && set.b <= cutoff
-        && set.c >  cutoff
-        && set.d <= cutoff
-}
+// Fraction of the way from p1 to p2 at which the side is placed
+fn side_fn(p1: f32, p2: f32, config: &MeshConfig) -> f32 {
+    if p1 == p2 {
+        return 0.5;
+    }
 
-fn check_pattern_4(set: &SquareSet, cutoff: f32) -> bool {
-    return set.a >  cutoff
-        && set.b >  cutoff
-        && set.c <= cutoff
-        && set.d >  cutoff
-}
+    let i = ((config.iso - p1) / (p2 - p1)).clamp(0.0, 1.0);
 
-fn check_pattern_5(set: &SquareSet, cutoff: f32) -> bool {
-    return set.a >= cutoff
-        && set.b >= cutoff
-        && set.c >= cutoff
-        && set.d >= cutoff
+    match config.interpolation {
+        Interpolation::Smooth => {
+            let smooth_i = smooth(i);
+            smooth_i / (smooth_i + smooth(1.0 - i))
+        }
+        Interpolation::Linear => i,
+    }
 }
 
-
 // Sides:
 // a-i>b
-// /   |
-// j . k
-// |   \
-// d<l-c
-
-fn pattern_1(set: SquareSet) -> Mesh {
-    // 0i-1
-    // j /.
-    // |/..
-    // 2...
-    // ....
-
-    let side_i = side_fn(set.a, set.b);
-    let side_j = side_fn(set.a, set.d);
-
-    Mesh {
-        vertices: vec![
-            Vertex { position: [-0.5, -0.5, 0.0], colour: [0.0, 0.0, 0.0] },
-            Vertex { position: [-0.5 + side_i, -0.5, 0.0], colour: [0.0, 0.0, 0.0] },
-            Vertex { position: [-0.5, -0.5 + side_j, 0.0], colour: [0.0, 0.0, 0.0] }
-        ],
-        indices: vec![
-            0, 1, 2
-        ]
-    }
+// j   k
+// v   v
+// d-l>c
+//
+// Sides run from a towards b/d and from d/b towards c so that a side shared
+// with a neighbouring set is evaluated in the same direction from both.
+
+// A sample or a side between two neighbouring samples, in set coordinates
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub(crate) enum GridKey {
+    Sample(i32, i32),
+    Side { x: i32, y: i32, along_x: bool },
 }
 
-fn pattern_2(set: SquareSet) -> Mesh {
-    // 0---1
-    // j / k
-    // 2---3
-    // .....
-    // .....
-
-    let side_j = side_fn(set.a, set.d);
-    let side_k = side_fn(set.b, set.c);
-
-    Mesh {
-        vertices: vec![
-            Vertex { position: [-0.5, -0.5, 0.0], colour: [0.0, 0.0, 0.0] },
-            Vertex { position: [0.5, -0.5, 0.0], colour: [0.0, 0.0, 0.0] },
-            Vertex { position: [-0.5, -0.5 + side_j, 0.0], colour: [0.0, 0.0, 0.0] },
-            Vertex { position: [0.5, -0.5 + side_k, 0.0], colour: [0.0, 0.0, 0.0] }
-        ],
-        indices: vec![
-            0, 1, 2,
-            1, 3, 2
-        ]
-    }
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub(crate) enum Point {
+    A, B, C, D,
+    I, J, K, L,
 }
 
-fn pattern_3(set: SquareSet) -> Mesh {
-    // 0i1..
-    // j /\.
-    // 2/| 3
-    // .\|/k
-    // ..4l5
-
-    let side_i = side_fn(set.a, set.b);
-    let side_j = side_fn(set.a, set.d);
-    let side_k = side_fn(set.c, set.b);
-    let side_l = side_fn(set.c, set.d);
-
-    Mesh {
-        vertices: vec![
-            Vertex { position: [-0.5, -0.5, 0.0], colour: [0.0, 0.0, 0.0] },
-            Vertex { position: [-0.5 + side_i, -0.5, 0.0], colour: [0.0, 0.0, 0.0] },
-            Vertex { position: [-0.5, -0.5 + side_j, 0.0], colour: [0.0, 0.0, 0.0] },
-            Vertex { position: [0.5, 0.5 - side_k, 0.0], colour: [0.0, 0.0, 0.0] },
-            Vertex { position: [0.5 - side_l, 0.5, 0.0], colour: [0.0, 0.0, 0.0] },
-            Vertex { position: [0.5, 0.5, 0.0], colour: [0.0, 0.0, 0.0] }
-        ],
-        indices: vec![
-            0, 1, 2,
-            1, 4, 2,
-            1, 3, 4,
-            3, 5, 4
-        ]
+impl Point {
+    pub(crate) fn is_side(&self) -> bool {
+        matches!(self, Point::I | Point::J | Point::K | Point::L)
     }
-}
 
-fn pattern_4(set: SquareSet) -> Mesh {
-    // 0-----1
-    // |\  \ k
-    // | \   3
-    // |  \ /
-    // 2-l-4
-
-    let side_k = side_fn(set.b, set.c);
-    let side_l = side_fn(set.d, set.c);
-
-    Mesh {
-        vertices: vec![
-            Vertex { position: [-0.5, -0.5, 0.0], colour: [0.0, 0.0, 0.0] },
-            Vertex { position: [0.5, -0.5, 0.0], colour: [0.0, 0.0, 0.0] },
-            Vertex { position: [-0.5, 0.5, 0.0], colour: [0.0, 0.0, 0.0] },
-            Vertex { position: [0.5, -0.5 + side_k, 0.0], colour: [0.0, 0.0, 0.0] },
-            Vertex { position: [-0.5 + side_l, 0.5, 0.0], colour: [0.0, 0.0, 0.0] }
-        ],
-        indices: vec![
-            0, 1, 3,
-            0, 3, 4,
-            0, 4, 2
-        ]
+    // Identifies this point for the set at column x, row y
+    pub(crate) fn grid_key(&self, x: i32, y: i32) -> GridKey {
+        match self {
+            Point::A => GridKey::Sample(x, y),
+            Point::B => GridKey::Sample(x + 1, y),
+            Point::C => GridKey::Sample(x + 1, y + 1),
+            Point::D => GridKey::Sample(x, y + 1),
+            Point::I => GridKey::Side { x, y, along_x: true },
+            Point::J => GridKey::Side { x, y, along_x: false },
+            Point::K => GridKey::Side { x: x + 1, y, along_x: false },
+            Point::L => GridKey::Side { x, y: y + 1, along_x: true },
+        }
     }
-}
 
-fn pattern_5(_set: SquareSet) -> Mesh {
-    Mesh {
-        vertices: vec![
-            Vertex { position: [-0.5, -0.5, 0.0], colour: [0.0, 0.0, 0.0] },
-            Vertex { position: [0.5, -0.5, 0.0], colour: [0.0, 0.0, 0.0] },
-            Vertex { position: [-0.5, 0.5, 0.0], colour: [0.0, 0.0, 0.0] },
-            Vertex { position: [0.5, 0.5, 0.0], colour: [0.0, 0.0, 0.0] }
-        ],
-        indices: vec![
-            0, 1, 2,
-            1, 3, 2
-        ]
+    pub(crate) fn position(&self, set: &SquareSet, config: &MeshConfig) -> [f32; 3] {
+        match self {
+            Point::A => [-0.5, -0.5, 0.0],
+            Point::B => [0.5, -0.5, 0.0],
+            Point::C => [0.5, 0.5, 0.0],
+            Point::D => [-0.5, 0.5, 0.0],
+            Point::I => [-0.5 + side_fn(set.a, set.b, config), -0.5, 0.0],
+            Point::J => [-0.5, -0.5 + side_fn(set.a, set.d, config), 0.0],
+            Point::K => [0.5, -0.5 + side_fn(set.b, set.c, config), 0.0],
+            Point::L => [-0.5 + side_fn(set.d, set.c, config), 0.5, 0.0],
+        }
     }
 }
+
+use Point::*;
+
+// Filled polygons for each case, wound anticlockwise.
+//
+//  0)..  1)#.  2).#  3)##  4)..  5)#.  6).#  7)##
+//    ..    ..    ..    ..    .#    .#    .#    .#
+//
+//  8)..  9)#. 10).# 11)## 12).. 13)#. 14).# 15)##
+//    #.    #.    #.    #.    ##    ##    ##    ##
+const CASES: [&[&[Point]]; 16] = [
+    &[],
+    &[&[A, I, J]],
+    &[&[I, B, K]],
+    &[&[A, B, K, J]],
+    &[&[K, C, L]],
+    &[&[A, I, K, C, L, J]],
+    &[&[I, B, C, L]],
+    &[&[A, B, C, L, J]],
+    &[&[L, D, J]],
+    &[&[A, I, L, D]],
+    &[&[I, B, K, L, D, J]],
+    &[&[A, B, K, L, D]],
+    &[&[K, C, D, J]],
+    &[&[A, I, K, C, D]],
+    &[&[I, B, C, D, J]],
+    &[&[A, B, C, D]],
+];
+
+// Cases 5 and 10 with the diagonal corners split apart
+const SEPARATED_SADDLES: [&[&[Point]]; 2] = [
+    &[&[A, I, J], &[K, C, L]],
+    &[&[I, B, K], &[L, D, J]],
+];
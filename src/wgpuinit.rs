@@ -5,13 +5,45 @@ use winit::{
 };
 use winit::window::Window;
 use wgpu::util::DeviceExt;
-use device_query::{DeviceState, DeviceQuery};
-use crate::{ Vertex, Plane, ReferencePoint };
+use std::collections::{ HashMap, HashSet };
+use std::path::PathBuf;
+use crate::brush::{ BlendMode, Capsule };
+use crate::camera::Camera;
+use crate::mesh::{ Mesh, MeshConfig, Vertex };
+use crate::plane::{ ChunkListener, Plane, ReferencePoint };
+
+// GPU copy of one chunk's cached mesh
+struct ChunkBuffers {
+    vertex_buffer: wgpu::Buffer,
+    index_buffer: wgpu::Buffer,
+    index_format: wgpu::IndexFormat,
+    num_indices: u32,
+}
+
+// Drops the GPU buffers of chunks the plane unloads
+struct BufferEvictions<'a>(&'a mut HashMap<(i32, i32), Option<ChunkBuffers>>);
+
+impl ChunkListener for BufferEvictions<'_> {
+    fn chunk_unloaded(&mut self, coord: (i32, i32)) {
+        self.0.remove(&coord);
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum Stroke {
+    Fill,
+    Carve,
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct CameraUniform {
+    view_proj: [[f32; 4]; 4],
+}
 
 struct State {
     surface: wgpu::Surface,
     device: wgpu::Device,
-    device_state: DeviceState,
     queue: wgpu::Queue,
     config: wgpu::SurfaceConfiguration,
     size: winit::dpi::PhysicalSize<u32>,
@@ -21,21 +53,30 @@ struct State {
     window: Window,
 
     plane: Plane,
+    // Where F5 saves the world
+    save_path: PathBuf,
     ref_point: ReferencePoint,
+    mesh_config: MeshConfig,
+    camera: Camera,
+    camera_buffer: wgpu::Buffer,
+    camera_bind_group: wgpu::BindGroup,
+
+    // Cursor position in physical pixels from the top left of the window
+    cursor: Option<(f32, f32)>,
+    stroke: Option<Stroke>,
+    // World position of the last brush dab in the current stroke
+    last_dab: Option<(f32, f32)>,
+    brush_radius: f32,
 
     render_pipeline: wgpu::RenderPipeline,
-    vertex_buffer: wgpu::Buffer,
-    index_buffer: wgpu::Buffer,
-    num_indices: u32,
+    // Buffers for each visible chunk, None where the chunk's mesh is empty
+    chunk_buffers: HashMap<(i32, i32), Option<ChunkBuffers>>,
 }
 
 impl State {
     // Creating some of the wgpu types requires async code
-    async fn new(window: Window, plane: Plane, ref_point: ReferencePoint) -> Self {
-        let mesh = plane.mesh_from_ref(&ref_point);
-        
-        let vertices: &[Vertex] = &mesh.vertices;
-        let indices: &[i16] = &mesh.indices;
+    async fn new(window: Window, plane: Plane, save_path: PathBuf, ref_point: ReferencePoint) -> Self {
+        let mesh_config = MeshConfig::default();
         let size = window.inner_size();
 
         // The instance is a handle to our GPU
@@ -75,7 +116,6 @@ impl State {
             None, // Trace path
         ).await.unwrap();
         
-        let device_state = DeviceState::new();
 
         let surface_caps = surface.get_capabilities(&adapter);
         // Shader code in this tutorial assumes an sRGB surface texture. Using a different
@@ -101,10 +141,48 @@ impl State {
             source: wgpu::ShaderSource::Wgsl(include_str!("shader.wgsl").into()),
         });
 
+        let mut camera = Camera::new(ref_point.position, 1.0 / ref_point.render_dist);
+        camera.aspect = size.width.max(1) as f32 / size.height.max(1) as f32;
+
+        let camera_buffer = device.create_buffer_init(
+            &wgpu::util::BufferInitDescriptor {
+                label: Some("Camera Buffer"),
+                contents: bytemuck::cast_slice(&[CameraUniform { view_proj: camera.view_proj() }]),
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            }
+        );
+
+        let camera_bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::VERTEX,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                }
+            ],
+            label: Some("Camera Bind Group Layout"),
+        });
+
+        let camera_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &camera_bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: camera_buffer.as_entire_binding(),
+                }
+            ],
+            label: Some("Camera Bind Group"),
+        });
+
         let render_pipeline_layout =
         device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
             label: Some("Render Pipeline Layout"),
-            bind_group_layouts: &[],
+            bind_group_layouts: &[&camera_bind_group_layout],
             push_constant_ranges: &[],
         });
 
@@ -148,39 +226,31 @@ impl State {
             multiview: None, // 5.
         });
 
-        let vertex_buffer = device.create_buffer_init(
-            &wgpu::util::BufferInitDescriptor {
-                label: Some("Vertex Buffer"),
-                contents: bytemuck::cast_slice(vertices),
-                usage: wgpu::BufferUsages::VERTEX,
-            }
-        );
-
-        let index_buffer = device.create_buffer_init(
-            &wgpu::util::BufferInitDescriptor {
-                label: Some("Index Buffer"),
-                contents: bytemuck::cast_slice(indices),
-                usage: wgpu::BufferUsages::INDEX,
-            }
-        );
-
-        let num_indices = indices.len() as u32;
-
-        Self {
+        let mut state = Self {
             window,
             surface,
             device,
-            device_state,
             queue,
             config,
             size,
             plane,
+            save_path,
             ref_point,
+            mesh_config,
+            camera,
+            camera_buffer,
+            camera_bind_group,
+            cursor: None,
+            stroke: None,
+            last_dab: None,
+            brush_radius: 3.0,
             render_pipeline,
-            vertex_buffer,
-            index_buffer,
-            num_indices,
-        }
+            chunk_buffers: HashMap::new(),
+        };
+        state.update_camera();
+        state.sync_chunk_buffers();
+
+        state
     }
 
     pub fn window(&self) -> &Window {
@@ -193,9 +263,25 @@ impl State {
             self.config.width = new_size.width;
             self.config.height = new_size.height;
             self.surface.configure(&self.device, &self.config);
+
+            self.camera.aspect = new_size.width as f32 / new_size.height as f32;
+            self.update_camera();
+            self.sync_chunk_buffers();
         }
     }
 
+    // Follows the reference point and widens its render distance to cover the view
+    fn update_camera(&mut self) {
+        self.camera.position = self.ref_point.position;
+        self.ref_point.render_dist = self.camera.view_radius();
+
+        self.queue.write_buffer(
+            &self.camera_buffer,
+            0,
+            bytemuck::cast_slice(&[CameraUniform { view_proj: self.camera.view_proj() }]),
+        );
+    }
+
     fn input(&mut self, event: &WindowEvent) -> bool {
         match event {
             WindowEvent::KeyboardInput {
@@ -224,69 +310,141 @@ impl State {
                     self.update();
                     true
                 },
+                VirtualKeyCode::PageUp => {
+                    self.camera.zoom *= 1.25;
+                    self.update_camera();
+                    self.sync_chunk_buffers();
+                    true
+                },
+                VirtualKeyCode::PageDown => {
+                    self.camera.zoom /= 1.25;
+                    self.update_camera();
+                    self.sync_chunk_buffers();
+                    true
+                },
+                VirtualKeyCode::Q => {
+                    self.camera.rotation += std::f32::consts::PI / 12.0;
+                    self.update_camera();
+                    true
+                },
+                VirtualKeyCode::E => {
+                    self.camera.rotation -= std::f32::consts::PI / 12.0;
+                    self.update_camera();
+                    true
+                },
+                VirtualKeyCode::F5 => {
+                    self.flush();
+                    match self.plane.save(&self.save_path) {
+                        Ok(()) => log::info!("saved world to {}", self.save_path.display()),
+                        Err(error) => log::error!("couldn't save world to {}: {}", self.save_path.display(), error),
+                    }
+                    true
+                },
                 _ => false
-                //println!("state: {0}, key: {1}", *state == ElementState::Pressed, match virtual_keycode {
-                //    VirtualKeyCode::Up => "up_key",
-                //    _ => "unkn"
-                //});
             },
-            _ => return false
+            WindowEvent::CursorMoved { position, .. } => {
+                self.cursor = Some((position.x as f32, position.y as f32));
+                self.paint_stroke();
+                true
+            },
+            WindowEvent::CursorLeft { .. } => {
+                self.cursor = None;
+                self.last_dab = None;
+                true
+            },
+            WindowEvent::MouseInput { state, button, .. } => {
+                let stroke = match button {
+                    MouseButton::Left => Stroke::Fill,
+                    MouseButton::Right => Stroke::Carve,
+                    _ => return false,
+                };
+
+                match state {
+                    ElementState::Pressed => {
+                        self.stroke = Some(stroke);
+                        self.last_dab = None;
+                        self.paint_stroke();
+                    },
+                    ElementState::Released if self.stroke == Some(stroke) => self.stroke = None,
+                    ElementState::Released => {},
+                }
+                true
+            },
+            WindowEvent::MouseWheel { delta, .. } => {
+                let lines = match delta {
+                    MouseScrollDelta::LineDelta(_, y) => *y,
+                    MouseScrollDelta::PixelDelta(position) => position.y as f32 / 20.0,
+                };
+                self.brush_radius = (self.brush_radius * 1.1_f32.powf(lines)).clamp(0.5, 256.0);
+                true
+            },
+            _ => false
         }
     }
 
-    fn update(&mut self) {
-        self.plane.paint_antialiased_filled_circle(self.ref_point.position.1, self.ref_point.position.0, 3.0);
-        println!("{}", self.ref_point.position.1);
-        let mut mesh = self.plane.mesh_from_ref(&self.ref_point);
+    fn flush(&mut self) {
+        if let Err(error) = self.plane.flush() {
+            log::error!("couldn't write chunks to their regions: {}", error);
+        }
+    }
 
-        //mesh.scale(0.25);
-        
-        let vertices: &[Vertex] = &mesh.vertices;
-        let indices: &[i16] = &mesh.indices;
+    fn update(&mut self) {
+        self.update_camera();
+        self.sync_chunk_buffers();
+    }
 
-        let vertex_buffer = self.device.create_buffer_init(
-            &wgpu::util::BufferInitDescriptor {
-                label: Some("Vertex Buffer"),
-                contents: bytemuck::cast_slice(vertices),
-                usage: wgpu::BufferUsages::VERTEX,
-            }
+    // World position under the cursor
+    fn cursor_world(&self) -> Option<(f32, f32)> {
+        let (x, y) = self.cursor?;
+        let clip = (
+            2.0 * x / self.size.width as f32 - 1.0,
+            1.0 - 2.0 * y / self.size.height as f32,
         );
 
-        let index_buffer = self.device.create_buffer_init(
-            &wgpu::util::BufferInitDescriptor {
-                label: Some("Index Buffer"),
-                contents: bytemuck::cast_slice(indices),
-                usage: wgpu::BufferUsages::INDEX,
-            }
-        );
+        Some(self.camera.clip_to_world(clip))
+    }
+
+    // Paints a capsule from the last dab to the cursor so fast drags leave no gaps
+    fn paint_stroke(&mut self) {
+        let (Some(stroke), Some(to)) = (self.stroke, self.cursor_world()) else { return };
+        let from = self.last_dab.unwrap_or(to);
 
-        let num_indices = indices.len() as u32;
+        let mode = match stroke {
+            Stroke::Fill => BlendMode::Fill,
+            Stroke::Carve => BlendMode::Carve,
+        };
+        self.plane.paint(&Capsule { from, to, radius: self.brush_radius }, mode, 1.0);
 
-        self.vertex_buffer = vertex_buffer;
-        self.index_buffer = index_buffer;
-        self.num_indices = num_indices;
+        self.last_dab = Some(to);
+        self.sync_chunk_buffers();
     }
 
-    fn render(&mut self) -> Result<(), wgpu::SurfaceError> {
-        let _keys = self.device_state.get_keys();
-        let mouse = self.device_state.get_mouse();
+    // Uploads chunks that were remeshed or came into view and frees those that left it
+    fn sync_chunk_buffers(&mut self) {
+        let ref_points = std::slice::from_ref(&self.ref_point);
+        if let Err(error) = self.plane.update_active(ref_points, &mut BufferEvictions(&mut self.chunk_buffers)) {
+            log::error!("couldn't stream chunks: {}", error);
+        }
 
-        let window_pos = match self.window.inner_position() {
-            Ok(i) => (i.x as f32 + 1.0, i.y as f32 + 1.0),
-            _ => (0.0, 0.0)
-        };
+        let remeshed: HashSet<(i32, i32)> = self.plane.remesh_dirty(&self.mesh_config).into_iter().collect();
 
-        let window_size = match self.window.inner_size() {
-            i => (i.width as f32, i.height as f32),
-        };
+        let (min, max) = self.plane.ref_region(&self.ref_point);
+        let visible: HashSet<(i32, i32)> = self.plane.chunks_in_region(min, max).map(|(coord, _)| coord).collect();
 
-        let _mouse_pos = (((mouse.coords.0 as f32 - window_pos.0)/(window_size.0)), ((mouse.coords.1 as f32 - window_pos.1)/(window_size.1)));
-        
-        if ((mouse.coords.0 as f32) <= window_pos.0 + window_size.0) && (mouse.coords.0 as f32 >= window_pos.0)
-        && ((mouse.coords.1 as f32) <= window_pos.1 + window_size.1) && (mouse.coords.1 as f32 >= window_pos.1) {
-            //println!("mouse: {0}, {1}", mouse_pos.0, mouse_pos.1);
+        self.chunk_buffers.retain(|coord, _| visible.contains(coord));
+
+        for coord in visible {
+            if self.chunk_buffers.contains_key(&coord) && !remeshed.contains(&coord) {
+                continue;
+            }
+
+            let Some(mesh) = self.plane.chunk_mesh(coord, &self.mesh_config) else { continue };
+            let buffers = create_chunk_buffers(&self.device, mesh);
+            self.chunk_buffers.insert(coord, buffers);
         }
+    }
 
+    fn render(&mut self) -> Result<(), wgpu::SurfaceError> {
         let output = self.surface.get_current_texture()?;
 
         let view = output.texture.create_view(&wgpu::TextureViewDescriptor::default());
@@ -315,9 +473,12 @@ impl State {
             });
 
             render_pass.set_pipeline(&self.render_pipeline); // 2.
-            render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
-            render_pass.set_index_buffer(self.index_buffer.slice(..), wgpu::IndexFormat::Uint16); // 1.
-            render_pass.draw_indexed(0..self.num_indices, 0, 0..1); // 3.
+            render_pass.set_bind_group(0, &self.camera_bind_group, &[]);
+            for buffers in self.chunk_buffers.values().flatten() {
+                render_pass.set_vertex_buffer(0, buffers.vertex_buffer.slice(..));
+                render_pass.set_index_buffer(buffers.index_buffer.slice(..), buffers.index_format); // 1.
+                render_pass.draw_indexed(0..buffers.num_indices, 0, 0..1); // 3.
+            }
         }
 
         // submit will accept anything that implements IntoIter
@@ -326,24 +487,9 @@ impl State {
     
         Ok(())
     }
-
-    fn mouse_pos(&self) -> (f32, f32) {
-        let mouse = self.device_state.get_mouse();
-
-        let window_pos = match self.window.inner_position() {
-            Ok(i) => (i.x as f32 + 1.0, i.y as f32 + 1.0),
-            _ => (0.0, 0.0)
-        };
-
-        let window_size = match self.window.inner_size() {
-            i => (i.width as f32, i.height as f32),
-        };
-
-        (((mouse.coords.0 as f32 - window_pos.0)/(window_size.0)), ((mouse.coords.1 as f32 - window_pos.1)/(window_size.1)))
-    }
 }
 
-pub async fn run(plane: Plane) {
+pub async fn run(plane: Plane, save_path: impl Into<PathBuf>) {
     env_logger::init();
     let event_loop = EventLoop::new();
     let window = WindowBuilder::new().build(&event_loop).unwrap();
@@ -356,9 +502,9 @@ pub async fn run(plane: Plane) {
     //let mesh = plane.mesh_from_ref(reference);
 
     //let vertices: &[Vertex] = &mesh.vertices;
-    //let indices: &[i16] = &mesh.indices;
+    //let indices: &[u32] = &mesh.indices;
     
-    let mut state = State::new(window, plane, reference).await;
+    let mut state = State::new(window, plane, save_path.into(), reference).await;
 
     //state.update(vertices, indices);
     
@@ -367,7 +513,7 @@ pub async fn run(plane: Plane) {
             Event::WindowEvent {
                 ref event,
                 window_id,
-            } if window_id == state.window().id() => if !state.input(event) { // UPDATED!
+            } if window_id == state.window().id() && !state.input(event) => {
                 match event {
                     WindowEvent::CloseRequested
                     | WindowEvent::KeyboardInput {
@@ -378,7 +524,10 @@ pub async fn run(plane: Plane) {
                                 ..
                             },
                         ..
-                    } => *control_flow = ControlFlow::Exit,
+                    } => {
+                        state.flush();
+                        *control_flow = ControlFlow::Exit;
+                    },
                     WindowEvent::Resized(physical_size) => {
                         state.resize(*physical_size);
                     }
@@ -410,6 +559,47 @@ pub async fn run(plane: Plane) {
     });
 }
 
+fn create_chunk_buffers(device: &wgpu::Device, mesh: &Mesh) -> Option<ChunkBuffers> {
+    if mesh.indices.is_empty() {
+        return None;
+    }
+
+    let vertex_buffer = device.create_buffer_init(
+        &wgpu::util::BufferInitDescriptor {
+            label: Some("Chunk Vertex Buffer"),
+            contents: bytemuck::cast_slice(&mesh.vertices),
+            usage: wgpu::BufferUsages::VERTEX,
+        }
+    );
+
+    let (index_buffer, index_format) = create_index_buffer(device, mesh);
+
+    Some(ChunkBuffers {
+        vertex_buffer,
+        index_buffer,
+        index_format,
+        num_indices: mesh.indices.len() as u32,
+    })
+}
+
+// Uploads the mesh's indices as u16 when they fit, falling back to u32
+fn create_index_buffer(device: &wgpu::Device, mesh: &Mesh) -> (wgpu::Buffer, wgpu::IndexFormat) {
+    let (contents, format) = match mesh.compact_indices() {
+        Some(indices) => (bytemuck::cast_slice(&indices).to_vec(), wgpu::IndexFormat::Uint16),
+        None => (bytemuck::cast_slice(&mesh.indices).to_vec(), wgpu::IndexFormat::Uint32),
+    };
+
+    let buffer = device.create_buffer_init(
+        &wgpu::util::BufferInitDescriptor {
+            label: Some("Chunk Index Buffer"),
+            contents: &contents,
+            usage: wgpu::BufferUsages::INDEX,
+        }
+    );
+
+    (buffer, format)
+}
+
 impl Vertex {
     fn desc() -> wgpu::VertexBufferLayout<'static> {
         wgpu::VertexBufferLayout {
@@ -0,0 +1,189 @@
+use std::fmt;
+use std::io::{ self, Read, Write };
+
+use crate::chunk::Chunk;
+
+// Files start with the magic and a version, then the seed, chunk size and chunk count,
+// all little endian. Each chunk follows as its coordinate, an encoding byte and its samples
+pub const MAGIC: [u8; 4] = *b"MSQW";
+pub const VERSION: u32 = 1;
+
+// Every sample has the same value, stored once
+const UNIFORM: u8 = 0;
+// Samples scaled between the chunk's min and max into u16s
+const QUANTISED: u8 = 1;
+
+// Larger sizes are taken as corruption rather than allocated
+const MAX_CHUNK_SIZE: usize = 4096;
+
+#[derive(Debug)]
+pub enum WorldError {
+    Io(io::Error),
+    // The file isn't a world file at all
+    BadMagic([u8; 4]),
+    // Written by another version of the format
+    UnsupportedVersion(u32),
+    // The file ended in the middle of the data
+    Truncated,
+    // The data doesn't make sense, with a description of what was wrong
+    Corrupt(String),
+}
+
+impl fmt::Display for WorldError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            WorldError::Io(error) => write!(f, "world file io error: {}", error),
+            WorldError::BadMagic(magic) => write!(f, "not a world file, starts with {:?}", magic),
+            WorldError::UnsupportedVersion(version) => {
+                write!(f, "world file version {} isn't supported, expected version {}", version, VERSION)
+            },
+            WorldError::Truncated => write!(f, "world file ends unexpectedly"),
+            WorldError::Corrupt(reason) => write!(f, "world file is corrupt: {}", reason),
+        }
+    }
+}
+
+impl std::error::Error for WorldError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            WorldError::Io(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for WorldError {
+    fn from(error: io::Error) -> Self {
+        if error.kind() == io::ErrorKind::UnexpectedEof {
+            WorldError::Truncated
+        } else {
+            WorldError::Io(error)
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub(crate) struct Header {
+    pub seed: i32,
+    pub chunk_size: usize,
+    pub chunk_count: usize,
+}
+
+pub(crate) fn write_header(writer: &mut impl Write, header: &Header) -> Result<(), WorldError> {
+    writer.write_all(&MAGIC)?;
+    writer.write_all(&VERSION.to_le_bytes())?;
+    writer.write_all(&header.seed.to_le_bytes())?;
+    writer.write_all(&(header.chunk_size as u32).to_le_bytes())?;
+    writer.write_all(&(header.chunk_count as u32).to_le_bytes())?;
+
+    Ok(())
+}
+
+pub(crate) fn read_header(reader: &mut impl Read) -> Result<Header, WorldError> {
+    let mut magic = [0; 4];
+    reader.read_exact(&mut magic)?;
+    if magic != MAGIC {
+        return Err(WorldError::BadMagic(magic));
+    }
+
+    let version = read_u32(reader)?;
+    if version != VERSION {
+        return Err(WorldError::UnsupportedVersion(version));
+    }
+
+    let seed = read_u32(reader)? as i32;
+    let chunk_size = read_u32(reader)? as usize;
+    if chunk_size == 0 || chunk_size > MAX_CHUNK_SIZE {
+        return Err(WorldError::Corrupt(format!("chunk size {} is out of range", chunk_size)));
+    }
+
+    Ok(Header {
+        seed,
+        chunk_size,
+        chunk_count: read_u32(reader)? as usize,
+    })
+}
+
+// Uniform chunks take 13 bytes, any other chunk two bytes per sample plus 17
+pub(crate) fn write_chunk(writer: &mut impl Write, coord: (i32, i32), chunk: &Chunk) -> Result<(), WorldError> {
+    writer.write_all(&coord.0.to_le_bytes())?;
+    writer.write_all(&coord.1.to_le_bytes())?;
+
+    let samples = chunk.as_slice();
+    if samples.iter().all(|&value| value == samples[0]) {
+        writer.write_all(&[UNIFORM])?;
+        writer.write_all(&samples[0].to_le_bytes())?;
+        return Ok(());
+    }
+
+    let min = samples.iter().copied().fold(f32::INFINITY, f32::min);
+    let max = samples.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+
+    writer.write_all(&[QUANTISED])?;
+    writer.write_all(&min.to_le_bytes())?;
+    writer.write_all(&max.to_le_bytes())?;
+
+    let mut quantised = Vec::with_capacity(samples.len() * 2);
+    for value in samples {
+        let level = ((value - min) / (max - min) * u16::MAX as f32).round() as u16;
+        quantised.extend(level.to_le_bytes());
+    }
+    writer.write_all(&quantised)?;
+
+    Ok(())
+}
+
+pub(crate) fn read_chunk(reader: &mut impl Read, chunk_size: usize) -> Result<((i32, i32), Chunk), WorldError> {
+    let coord = (read_u32(reader)? as i32, read_u32(reader)? as i32);
+
+    let mut encoding = [0];
+    reader.read_exact(&mut encoding)?;
+
+    let chunk = match encoding[0] {
+        UNIFORM => {
+            let value = read_f32(reader)?;
+            if !value.is_finite() {
+                return Err(WorldError::Corrupt(format!("chunk {:?} is filled with {}", coord, value)));
+            }
+
+            Chunk::filled(chunk_size, value)
+        },
+        QUANTISED => {
+            let (min, max) = (read_f32(reader)?, read_f32(reader)?);
+            if !min.is_finite() || !max.is_finite() || min > max {
+                return Err(WorldError::Corrupt(format!("chunk {:?} has range {} to {}", coord, min, max)));
+            }
+
+            let mut quantised = vec![0; chunk_size * chunk_size * 2];
+            reader.read_exact(&mut quantised)?;
+
+            let samples = quantised.chunks_exact(2)
+                .map(|bytes| {
+                    let level = u16::from_le_bytes([bytes[0], bytes[1]]);
+                    // The ends are exact, so empty and solid samples survive unchanged
+                    match level {
+                        0 => min,
+                        u16::MAX => max,
+                        level => min + (max - min) * level as f32 / u16::MAX as f32,
+                    }
+                })
+                .collect();
+
+            Chunk::from_vec(chunk_size, samples)
+        },
+        encoding => return Err(WorldError::Corrupt(format!("chunk {:?} has unknown encoding {}", coord, encoding))),
+    };
+
+    Ok((coord, chunk))
+}
+
+fn read_u32(reader: &mut impl Read) -> Result<u32, WorldError> {
+    let mut bytes = [0; 4];
+    reader.read_exact(&mut bytes)?;
+
+    Ok(u32::from_le_bytes(bytes))
+}
+
+fn read_f32(reader: &mut impl Read) -> Result<f32, WorldError> {
+    Ok(f32::from_bits(read_u32(reader)?))
+}
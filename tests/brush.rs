@@ -0,0 +1,232 @@
+use marching_squares_rust_opengl::brush::coverage;
+use marching_squares_rust_opengl::{BlendMode, Brush, Capsule, Chunk, Circle, Plane, Polygon, Rectangle, Ring};
+
+// A circle of radius 4 at (8, 8) with a falloff of 2, over density 0.5. The centre is
+// fully covered, samples 1 inside the edge half covered and samples outside untouched
+const CIRCLE: Circle = Circle { centre: (8.0, 8.0), radius: 4.0 };
+const FULL: [(usize, usize); 1] = [(8, 8)];
+const HALF: [(usize, usize); 3] = [(11, 8), (8, 5), (5, 8)];
+const UNTOUCHED: [(usize, usize); 3] = [(13, 8), (8, 3), (0, 0)];
+
+fn painted_chunk(mode: BlendMode, falloff: f32) -> Chunk {
+    let mut chunk = Chunk::filled(16, 0.5);
+    chunk.paint(&CIRCLE, mode, falloff);
+
+    chunk
+}
+
+// The same circle painted across the four chunks it straddles
+fn painted_plane(mode: BlendMode, falloff: f32) -> Plane {
+    let mut plane = Plane::with_chunk_size(8);
+    for coord in [(0, 0), (1, 0), (0, 1), (1, 1)] {
+        plane.set_chunk(coord, Chunk::filled(8, 0.5));
+    }
+    plane.paint(&CIRCLE, mode, falloff);
+
+    plane
+}
+
+fn assert_painted(mode: BlendMode, full: f32, half: f32) {
+    let chunk = painted_chunk(mode, 2.0);
+    let plane = painted_plane(mode, 2.0);
+
+    let expected = FULL.iter().map(|&point| (point, full))
+        .chain(HALF.iter().map(|&point| (point, half)))
+        .chain(UNTOUCHED.iter().map(|&point| (point, 0.5)));
+
+    for ((x, y), value) in expected {
+        assert!((chunk.get(x, y) - value).abs() < 1e-6, "{:?} gave {} at ({}, {}) instead of {}", mode, chunk.get(x, y), x, y, value);
+    }
+    for y in 0..16 {
+        for x in 0..16 {
+            assert_eq!(plane.sample(x as i32, y as i32), Some(chunk.get(x, y)), "{:?} differs on the plane at ({}, {})", mode, x, y);
+        }
+    }
+}
+
+#[test]
+fn blend_modes_on_chunks_and_planes() {
+    assert_painted(BlendMode::Carve, 0.0, 0.5);
+    assert_painted(BlendMode::Fill, 1.0, 0.5);
+    assert_painted(BlendMode::Set(0.2), 0.2, 0.35);
+    assert_painted(BlendMode::Add(0.4), 0.9, 0.7);
+    assert_painted(BlendMode::Subtract(0.4), 0.1, 0.3);
+    assert_painted(BlendMode::Multiply(0.5), 0.25, 0.375);
+    // Far from the existing density the smooth union is a plain maximum, close to it it bulges
+    assert_painted(BlendMode::SmoothUnion(0.5), 1.0, 0.5625);
+}
+
+#[test]
+fn add_and_subtract_stay_in_range() {
+    assert_eq!(BlendMode::Add(0.4).blend(0.9, 1.0), 1.0);
+    assert_eq!(BlendMode::Subtract(0.4).blend(0.1, 1.0), 0.0);
+}
+
+#[test]
+fn smooth_union_limits() {
+    for value in [0.0, 0.3, 0.5, 0.9] {
+        for covered in [0.0, 0.25, 0.5, 1.0] {
+            let fill = BlendMode::Fill.blend(value, covered);
+
+            // No width is a plain fill, and a smooth union never goes below one
+            assert_eq!(BlendMode::SmoothUnion(0.0).blend(value, covered), fill);
+            assert!(BlendMode::SmoothUnion(0.5).blend(value, covered) >= fill);
+        }
+    }
+
+    // Values further apart than the width aren't rounded at all
+    assert_eq!(BlendMode::SmoothUnion(0.2).blend(0.0, 1.0), 1.0);
+    assert_eq!(BlendMode::SmoothUnion(0.2).blend(1.0, 0.5), 1.0);
+    // Untouched samples stay untouched
+    assert_eq!(BlendMode::SmoothUnion(0.5).blend(0.4, 0.0), 0.4);
+}
+
+#[test]
+fn no_falloff_gives_a_hard_edge() {
+    assert_eq!(coverage(-0.001, 0.0), 1.0);
+    assert_eq!(coverage(0.0, 0.0), 0.0);
+    assert_eq!(coverage(-0.001, -1.0), 1.0);
+    assert_eq!(coverage(-1.0, 2.0), 0.5);
+    assert_eq!(coverage(-3.0, 2.0), 1.0);
+
+    let chunk = painted_chunk(BlendMode::Set(0.25), 0.0);
+    let plane = painted_plane(BlendMode::Set(0.25), 0.0);
+
+    // Every sample strictly inside is fully set, the one on the edge is untouched
+    for (x, y) in FULL.into_iter().chain(HALF) {
+        assert_eq!(chunk.get(x, y), 0.25);
+        assert_eq!(plane.sample(x as i32, y as i32), Some(0.25));
+    }
+    assert_eq!(chunk.get(12, 8), 0.5);
+    assert_eq!(plane.sample(12, 8), Some(0.5));
+}
+
+#[test]
+fn plane_paints_only_the_chunks_a_stroke_covers() {
+    let mut plane = Plane::with_chunk_size(8);
+    plane.paint(&Capsule::line((0.0, 0.0), (80.0, 80.0), 2.0), BlendMode::Carve, 0.0);
+
+    // The stroke's bounds span 12 by 12 chunks, but only those along the diagonal are reached
+    assert!(plane.total_chunks() < 40, "{} chunks loaded", plane.total_chunks());
+    for (coord, chunk) in plane.chunks() {
+        assert!(chunk.as_slice().iter().any(|&value| value < 1.0), "chunk {:?} loaded but not painted", coord);
+    }
+}
+
+fn assert_distances(brush: &impl Brush, expected: &[((f32, f32), f32)]) {
+    for &((x, y), distance) in expected {
+        assert!((brush.distance(x, y) - distance).abs() < 1e-5, "distance at ({}, {}) is {} instead of {}", x, y, brush.distance(x, y), distance);
+    }
+}
+
+// The bounds must hold every point inside the shape, checked on a fine grid around them
+fn assert_bounds(brush: &impl Brush, expected: ((f32, f32), (f32, f32))) {
+    let (min, max) = brush.bounds();
+    let close = |a: (f32, f32), b: (f32, f32)| (a.0 - b.0).abs() < 1e-5 && (a.1 - b.1).abs() < 1e-5;
+    assert!(close(min, expected.0) && close(max, expected.1), "bounds {:?} instead of {:?}", (min, max), expected);
+
+    for i in 0..=80 {
+        for j in 0..=80 {
+            let x = min.0 - 1.0 + (max.0 - min.0 + 2.0) * i as f32 / 80.0;
+            let y = min.1 - 1.0 + (max.1 - min.1 + 2.0) * j as f32 / 80.0;
+            if brush.distance(x, y) < 0.0 {
+                assert!(x >= min.0 && x <= max.0 && y >= min.1 && y <= max.1, "({}, {}) is inside but out of bounds", x, y);
+            }
+        }
+    }
+}
+
+#[test]
+fn rotated_rectangle_distances() {
+    // 4 wide and 2 tall, turned a quarter so it stands upright
+    let rectangle = Rectangle::new((1.0, 1.0), (4.0, 2.0)).rotated(std::f32::consts::FRAC_PI_2);
+
+    assert_distances(&rectangle, &[
+        ((1.0, 1.0), -1.0),
+        ((1.0, 2.5), -0.5),
+        ((2.5, 1.0), 0.5),
+        ((1.0, 4.0), 1.0),
+        ((3.0, 4.0), std::f32::consts::SQRT_2),
+    ]);
+    assert_bounds(&rectangle, ((0.0, -1.0), (2.0, 3.0)));
+}
+
+#[test]
+fn capsule_distances() {
+    let capsule = Capsule::line((0.0, 0.0), (10.0, 0.0), 2.0);
+
+    assert_distances(&capsule, &[
+        ((5.0, 0.0), -1.0),
+        ((5.0, 1.0), 0.0),
+        ((5.0, -3.0), 2.0),
+        ((12.0, 0.0), 1.0),
+        ((-1.0, 0.0), 0.0),
+        ((-3.0, 4.0), 4.0),
+    ]);
+    assert_bounds(&capsule, ((-1.0, -1.0), (11.0, 1.0)));
+}
+
+#[test]
+fn concave_polygon_distances_in_either_winding() {
+    // A U with its notch between x = 2 and 4 above y = 2
+    let points = vec![(0.0, 0.0), (6.0, 0.0), (6.0, 6.0), (4.0, 6.0), (4.0, 2.0), (2.0, 2.0), (2.0, 6.0), (0.0, 6.0)];
+    let expected = [
+        ((1.0, 4.0), -1.0),
+        ((3.0, 1.0), -1.0),
+        ((3.0, 4.0), 1.0),
+        ((3.0, 2.5), 0.5),
+        ((8.0, 3.0), 2.0),
+    ];
+
+    let polygon = Polygon::new(points.clone());
+    assert_distances(&polygon, &expected);
+    assert_bounds(&polygon, ((0.0, 0.0), (6.0, 6.0)));
+
+    let reversed = Polygon::new(points.into_iter().rev().collect());
+    assert_distances(&reversed, &expected);
+}
+
+#[test]
+fn self_intersecting_polygon_uses_even_odd() {
+    // A pentagram, whose middle is crossed twice and so counts as outside
+    let star = Polygon::new((0..5).map(|i| {
+        let angle = i as f32 * 4.0 * std::f32::consts::PI / 5.0;
+        (5.0 * angle.sin(), -5.0 * angle.cos())
+    }).collect());
+
+    assert!(star.distance(0.0, 0.0) > 0.0);
+    assert!(star.distance(0.0, -3.5) < 0.0);
+    assert!(star.distance(0.0, -6.0) > 0.0);
+}
+
+#[test]
+fn ring_distances() {
+    let ring = Ring { centre: (2.0, 2.0), inner_radius: 2.0, outer_radius: 4.0 };
+
+    assert_distances(&ring, &[
+        ((2.0, 2.0), 2.0),
+        ((5.0, 2.0), -1.0),
+        ((2.0, -1.0), -1.0),
+        ((2.0, 3.5), 0.5),
+        ((7.0, 2.0), 1.0),
+    ]);
+    assert_bounds(&ring, ((-2.0, -2.0), (6.0, 6.0)));
+}
+
+#[test]
+fn polygon_across_a_chunk_border_matches_one_chunk() {
+    let triangle = Polygon::new(vec![(3.5, 4.0), (12.5, 5.0), (7.0, 13.0)]);
+
+    let mut chunk = Chunk::filled(16, 1.0);
+    chunk.paint(&triangle, BlendMode::Carve, 1.5);
+
+    let mut plane = Plane::with_chunk_size(8);
+    plane.paint(&triangle, BlendMode::Carve, 1.5);
+
+    assert_eq!(plane.total_chunks(), 4);
+    for y in 0..16 {
+        for x in 0..16 {
+            assert_eq!(plane.sample(x as i32, y as i32), Some(chunk.get(x, y)), "mismatch at ({}, {})", x, y);
+        }
+    }
+}
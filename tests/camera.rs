@@ -0,0 +1,45 @@
+use marching_squares_rust_opengl::Camera;
+
+fn camera() -> Camera {
+    Camera { position: (12.5, -3.0), zoom: 0.05, rotation: 0.7, aspect: 16.0 / 9.0 }
+}
+
+// Clip position of a world position, through the column major matrix
+fn to_clip(camera: &Camera, world: (f32, f32)) -> (f32, f32) {
+    let m = camera.view_proj();
+
+    (
+        m[0][0] * world.0 + m[1][0] * world.1 + m[3][0],
+        m[0][1] * world.0 + m[1][1] * world.1 + m[3][1],
+    )
+}
+
+fn close(a: (f32, f32), b: (f32, f32)) -> bool {
+    (a.0 - b.0).abs() < 1e-3 && (a.1 - b.1).abs() < 1e-3
+}
+
+#[test]
+fn clip_to_world_inverts_view_proj() {
+    let camera = camera();
+
+    assert!(close(to_clip(&camera, camera.position), (0.0, 0.0)));
+    for world in [(0.0, 0.0), (20.0, 4.0), (-7.5, -30.0), (12.5, 10.0)] {
+        let clip = to_clip(&camera, world);
+        assert!(close(camera.clip_to_world(clip), world), "{:?} came back as {:?}", world, camera.clip_to_world(clip));
+    }
+}
+
+#[test]
+fn view_radius_reaches_the_corners() {
+    let camera = camera();
+    let distance = |world: (f32, f32)| ((world.0 - camera.position.0).powi(2) + (world.1 - camera.position.1).powi(2)).sqrt();
+
+    // Every corner of the view is exactly as far as the radius, and the sides are closer
+    for corner in [(-1.0, -1.0), (1.0, -1.0), (1.0, 1.0), (-1.0, 1.0)] {
+        let world = camera.clip_to_world(corner);
+        assert!((distance(world) - camera.view_radius()).abs() < 1e-2, "corner {:?} is {} away", corner, distance(world));
+    }
+    for side in [(1.0, 0.0), (0.0, 1.0)] {
+        assert!(distance(camera.clip_to_world(side)) < camera.view_radius());
+    }
+}
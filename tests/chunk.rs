@@ -0,0 +1,22 @@
+use marching_squares_rust_opengl::Chunk;
+
+#[test]
+fn rows_cover_every_sample() {
+    let chunk = Chunk::from_vec(3, (0..9).map(|i| i as f32).collect());
+
+    let rows: Vec<&[f32]> = chunk.rows().collect();
+    assert_eq!(rows, vec![&[0.0, 1.0, 2.0][..], &[3.0, 4.0, 5.0], &[6.0, 7.0, 8.0]]);
+    assert_eq!(Chunk::new(1).rows().count(), 1);
+}
+
+#[test]
+#[should_panic(expected = "chunk size must be at least one sample")]
+fn empty_chunks_are_rejected() {
+    Chunk::new(0);
+}
+
+#[test]
+#[should_panic(expected = "chunk size must be at least one sample")]
+fn empty_chunks_from_vec_are_rejected() {
+    Chunk::from_vec(0, vec![]);
+}
@@ -0,0 +1,58 @@
+use marching_squares_rust_opengl::{BlendMode, Chunk, Circle, Empty, MeshConfig, Plane, Ring};
+
+fn empty_plane() -> Plane {
+    Plane::with_chunk_size(16).with_generator(Empty)
+}
+
+fn distance(point: [f32; 2], centre: (f32, f32)) -> f32 {
+    ((point[0] - centre.0).powi(2) + (point[1] - centre.1).powi(2)).sqrt()
+}
+
+#[test]
+fn circle_across_a_chunk_border_is_one_closed_loop() {
+    let mut plane = empty_plane();
+    plane.paint(&Circle { centre: (16.0, 8.0), radius: 5.0 }, BlendMode::Fill, 1.0);
+
+    let contours = plane.contours(&MeshConfig::default());
+
+    assert_eq!(contours.len(), 1);
+    assert!(contours[0].is_outer());
+    for &point in &contours[0].points {
+        assert!((distance(point, (16.0, 8.0)) - 4.5).abs() < 1.0, "point {:?} is off the circle", point);
+    }
+}
+
+#[test]
+fn ring_gives_an_outer_loop_and_a_hole() {
+    let mut plane = empty_plane();
+    let ring = Ring { centre: (16.0, 16.0), inner_radius: 5.0, outer_radius: 10.0 };
+    plane.paint(&ring, BlendMode::Fill, 0.0);
+
+    let contours = plane.contours(&MeshConfig::default());
+
+    assert_eq!(contours.len(), 2);
+    let outer = contours.iter().find(|contour| contour.is_outer()).expect("no outer loop");
+    let hole = contours.iter().find(|contour| contour.is_hole()).expect("no hole");
+    assert!(outer.signed_area() > -hole.signed_area());
+    assert!(hole.points.iter().all(|&point| distance(point, (16.0, 16.0)) < 6.0));
+}
+
+#[test]
+fn shape_running_off_the_loaded_data_stays_open() {
+    // Half a circle sits in the chunk, the rest would be in chunks that aren't loaded
+    let mut chunk = Chunk::filled(16, 0.0);
+    chunk.paint_circle(0.0, 8.0, 4.0, BlendMode::Fill, 0.0);
+
+    let mut plane = empty_plane();
+    plane.set_chunk((0, 0), chunk);
+
+    let contours = plane.contours(&MeshConfig::default());
+
+    assert_eq!(contours.len(), 1);
+    let contour = &contours[0];
+    assert!(!contour.closed);
+    for end in [contour.points[0], contour.points[contour.points.len() - 1]] {
+        assert!(end[0].abs() < 1e-4, "open end {:?} isn't on the data's edge", end);
+        assert!((end[1] - 8.0).abs() < 4.5);
+    }
+}
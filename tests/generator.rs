@@ -0,0 +1,65 @@
+use std::sync::{ Arc, Mutex };
+
+use image::{ DynamicImage, GrayImage, Luma };
+use marching_squares_rust_opengl::{BlendMode, Chunk, ChunkGenerator, Circle, Empty, ImageGenerator, ImageOptions, Plane, Solid};
+
+// Seed, coordinate and chunk size of one call
+type Call = (i32, (i32, i32), usize);
+
+// Records every call and fills chunks with a value derived from their coordinate
+#[derive(Clone, Default)]
+struct Recorder {
+    calls: Arc<Mutex<Vec<Call>>>,
+}
+
+impl ChunkGenerator for Recorder {
+    fn generate(&self, seed: i32, coord: (i32, i32), chunk: &mut Chunk) {
+        self.calls.lock().unwrap().push((seed, coord, chunk.size()));
+        chunk.as_mut_slice().fill((coord.0 * 10 + coord.1) as f32 / 100.0);
+    }
+}
+
+#[test]
+fn custom_generator_fills_the_planes_chunks() {
+    let recorder = Recorder::default();
+    let mut plane = Plane::with_chunk_size(8).with_seed(5).with_generator(recorder.clone());
+
+    plane.get_or_gen_chunk((1, -2)).unwrap();
+    plane.get_or_gen_chunk((1, -2)).unwrap();
+    plane.paint(&Circle { centre: (4.0, 12.0), radius: 1.5 }, BlendMode::Carve, 0.0);
+
+    // Each chunk is generated once, with the plane's seed and chunk size
+    assert_eq!(*recorder.calls.lock().unwrap(), vec![(5, (1, -2), 8), (5, (0, 1), 8)]);
+    assert_eq!(plane.sample(8, -16), Some(0.08));
+    assert_eq!(plane.sample(0, 8), Some(0.01));
+    assert_eq!(plane.sample(4, 12), Some(0.0));
+}
+
+#[test]
+fn empty_and_solid_ignore_what_was_there() {
+    let mut chunk = Chunk::filled(4, 0.5);
+    Empty.generate(3, (2, 2), &mut chunk);
+    assert!(chunk.as_slice().iter().all(|&value| value == 0.0));
+
+    Solid.generate(3, (2, 2), &mut chunk);
+    assert!(chunk.as_slice().iter().all(|&value| value == 1.0));
+
+    // Planes start solid unless told otherwise
+    assert!(Plane::with_chunk_size(4).get_or_gen_chunk((-3, 7)).unwrap().as_slice().iter().all(|&value| value == 1.0));
+    assert!(Plane::with_chunk_size(4).with_generator(Empty).get_or_gen_chunk((-3, 7)).unwrap().as_slice().iter().all(|&value| value == 0.0));
+}
+
+#[test]
+fn image_generator_places_the_image_in_world_samples() {
+    let image = DynamicImage::ImageLuma8(GrayImage::from_fn(2, 2, |x, y| Luma([if (x + y) % 2 == 0 { 255 } else { 0 }])));
+    let options = ImageOptions { origin: (9.0, 2.0), bilinear: false, outside: 0.25, ..Default::default() };
+    let mut plane = Plane::with_chunk_size(8).with_generator(ImageGenerator::new(&image, options));
+
+    let chunk = plane.get_or_gen_chunk((1, 0)).unwrap();
+    assert_eq!(chunk.get(1, 2), 1.0);
+    assert_eq!(chunk.get(2, 2), 0.0);
+    assert_eq!(chunk.get(1, 3), 0.0);
+    assert_eq!(chunk.get(2, 3), 1.0);
+    assert_eq!(chunk.get(0, 0), 0.25);
+    assert!(plane.get_or_gen_chunk((0, 0)).unwrap().as_slice().iter().all(|&value| value == 0.25));
+}
@@ -0,0 +1,107 @@
+use image::{DynamicImage, GrayImage, Luma, Rgba, RgbaImage};
+use marching_squares_rust_opengl::{Channel, Chunk, ExportOptions, ImageOptions, ImageSampler, Plane};
+
+fn close(a: f32, b: f32) -> bool {
+    (a - b).abs() < 1e-6
+}
+
+#[test]
+fn exported_chunk_loads_back_unchanged() {
+    let mut chunk = Chunk::new(24);
+    chunk.paint_antialiased_filled_circle(10.3, 12.7, 7.5);
+
+    let image = DynamicImage::ImageRgb8(chunk.to_image(&ExportOptions::default()));
+    let loaded = Chunk::from_image(&image, 24, ImageOptions::default());
+
+    for (exported, loaded) in chunk.as_slice().iter().zip(loaded.as_slice()) {
+        assert!((exported - loaded).abs() <= 0.5 / 255.0 + 1e-6, "{} became {}", exported, loaded);
+    }
+}
+
+#[test]
+fn plane_region_marks_chunk_grid_and_missing_chunks() {
+    let mut plane = Plane::with_chunk_size(8);
+    plane.paint_antialiased_filled_circle(0.0, 0.0, 3.0);
+
+    let options = ExportOptions { grid: Some([255, 0, 0]), ..ExportOptions::default() };
+    let image = plane.region_to_image((-4, -4), (11, 3), &options);
+
+    assert_eq!(image.dimensions(), (16, 8));
+    // Sample (0, 0) starts a chunk, so its column and row are grid lines
+    assert_eq!(image.get_pixel(4, 1).0, [255, 0, 0]);
+    assert_eq!(image.get_pixel(1, 4).0, [255, 0, 0]);
+    // The painted circle carved out the samples next to the origin
+    assert_eq!(image.get_pixel(5, 5).0, [0, 0, 0]);
+    // Chunk (1, 0) was never loaded
+    assert_eq!(image.get_pixel(13, 6).0, options.missing);
+}
+
+#[test]
+fn channels_and_inversion_pick_the_density() {
+    // A coloured pixel and a grey one
+    let image = DynamicImage::ImageRgba8(RgbaImage::from_fn(2, 1, |x, _| match x {
+        0 => Rgba([255, 102, 0, 51]),
+        _ => Rgba([153, 153, 153, 255]),
+    }));
+    let density = |channel, invert, x| {
+        ImageSampler::new(&image, ImageOptions { channel, invert, bilinear: false, ..Default::default() }).density(x, 0.0)
+    };
+
+    assert!(close(density(Channel::Red, false, 0.0), 1.0));
+    assert!(close(density(Channel::Green, false, 0.0), 0.4));
+    assert!(close(density(Channel::Blue, false, 0.0), 0.0));
+    assert!(close(density(Channel::Alpha, false, 0.0), 0.2));
+    assert!(close(density(Channel::Luminance, false, 1.0), 0.6));
+
+    assert!(close(density(Channel::Red, true, 0.0), 0.0));
+    assert!(close(density(Channel::Green, true, 0.0), 0.6));
+    assert!(close(density(Channel::Luminance, true, 1.0), 0.4));
+}
+
+#[test]
+fn flip_y_puts_the_top_row_last() {
+    let image = DynamicImage::ImageLuma8(GrayImage::from_fn(1, 2, |_, y| Luma([if y == 0 { 255 } else { 0 }])));
+
+    let upright = ImageSampler::new(&image, ImageOptions::default());
+    let flipped = ImageSampler::new(&image, ImageOptions { flip_y: true, ..Default::default() });
+
+    assert_eq!((upright.density(0.0, 0.0), upright.density(0.0, 1.0)), (1.0, 0.0));
+    assert_eq!((flipped.density(0.0, 0.0), flipped.density(0.0, 1.0)), (0.0, 1.0));
+}
+
+#[test]
+fn origin_and_scale_place_pixel_centres() {
+    let image = DynamicImage::ImageLuma8(GrayImage::from_fn(2, 1, |x, _| Luma([if x == 0 { 0 } else { 255 }])));
+    let options = ImageOptions { origin: (10.0, 20.0), scale: 2.0, outside: 0.75, ..Default::default() };
+
+    let nearest = ImageSampler::new(&image, ImageOptions { bilinear: false, ..options });
+    let bilinear = ImageSampler::new(&image, options);
+
+    // Pixel centres sit at x = 10 and 12, each pixel two samples wide
+    assert_eq!(bilinear.bounds(), ((9.0, 19.0), (13.0, 21.0)));
+    assert_eq!(nearest.density(10.9, 20.0), 0.0);
+    assert_eq!(nearest.density(11.1, 20.0), 1.0);
+    assert!(close(bilinear.density(11.0, 20.0), 0.5));
+    assert!(close(bilinear.density(10.5, 20.5), 0.25));
+    // Past the outer centres the edge pixels are held, past the bounds it's outside
+    assert_eq!(bilinear.density(12.9, 20.0), 1.0);
+    assert_eq!(bilinear.density(8.9, 20.0), 0.75);
+    assert_eq!(nearest.density(11.0, 21.5), 0.75);
+}
+
+#[test]
+fn plane_from_image_splits_it_across_chunks() {
+    let image = DynamicImage::ImageLuma8(GrayImage::from_fn(20, 12, |x, y| Luma([(x * 12 + y) as u8])));
+    let options = ImageOptions { bilinear: false, outside: 0.5, ..Default::default() };
+    let plane = Plane::from_image(&image, 8, options);
+
+    // The image's outer half pixels reach into the chunks at -1
+    assert_eq!(plane.total_chunks(), 4 * 3);
+    for y in 0..12 {
+        for x in 0..20 {
+            assert_eq!(plane.sample(x, y), Some((x * 12 + y) as f32 / 255.0), "mismatch at ({}, {})", x, y);
+        }
+    }
+    assert_eq!(plane.sample(-1, 0), Some(0.5));
+    assert_eq!(plane.sample(20, 15), Some(0.5));
+}
@@ -0,0 +1,72 @@
+use marching_squares_rust_opengl::{Chunk, Interpolation, MeshConfig, Meshable, SquareSet};
+
+// Positions of the vertices that sit on cell sides rather than on samples, in sample units
+fn edge_vertices(chunk: &Chunk, config: &MeshConfig) -> Vec<(f32, f32)> {
+    let mesh = SquareSet::chunk_to_sets(chunk).to_mesh(config);
+
+    mesh.vertices
+        .iter()
+        .map(|v| (v.position[0], v.position[1]))
+        .filter(|(x, y)| (x - x.round()).abs() > 1e-4 || (y - y.round()).abs() > 1e-4)
+        .collect()
+}
+
+fn linear() -> MeshConfig {
+    MeshConfig { iso: 0.0, interpolation: Interpolation::Linear, ..Default::default() }
+}
+
+#[test]
+fn linear_matches_straight_edge() {
+    let mut chunk = Chunk::new(33);
+    for x in 0..33 {
+        for y in 0..33 {
+            chunk.set(x, y, x as f32 - 10.3);
+        }
+    }
+
+    let vertices = edge_vertices(&chunk, &linear());
+
+    assert!(!vertices.is_empty());
+    for (x, _) in vertices {
+        assert!((x - 10.3).abs() < 1e-4, "vertex at {} instead of 10.3", x);
+    }
+}
+
+#[test]
+fn linear_matches_circle_sdf() {
+    let radius = 10.0;
+    let mut chunk = Chunk::new(33);
+    for x in 0..33 {
+        for y in 0..33 {
+            let distance = ((x as f32 - 16.0).powi(2) + (y as f32 - 16.0).powi(2)).sqrt();
+            chunk.set(x, y, distance - radius);
+        }
+    }
+
+    let vertices = edge_vertices(&chunk, &linear());
+
+    assert!(!vertices.is_empty());
+    for (x, y) in vertices {
+        let distance = ((x - 16.0).powi(2) + (y - 16.0).powi(2)).sqrt();
+        assert!((distance - radius).abs() < 0.02, "vertex at distance {} from the centre", distance);
+    }
+}
+
+#[test]
+fn linear_does_not_depend_on_side_direction() {
+    let mut rising = Chunk::new(33);
+    let mut falling = Chunk::new(33);
+    for x in 0..33 {
+        for y in 0..33 {
+            rising.set(x, y, y as f32 * 0.1);
+            falling.set(x, 32 - y, y as f32 * 0.1);
+        }
+    }
+
+    let config = MeshConfig { iso: 1.23, interpolation: Interpolation::Linear, ..Default::default() };
+    let rising_y = edge_vertices(&rising, &config)[0].1;
+    let falling_y = edge_vertices(&falling, &config)[0].1;
+
+    assert!((rising_y - 12.3).abs() < 1e-4);
+    assert!((falling_y - (32.0 - 12.3)).abs() < 1e-4);
+}
@@ -0,0 +1,38 @@
+use marching_squares_rust_opengl::{Mesh, Vertex};
+
+fn vertex(x: f32, y: f32) -> Vertex {
+    Vertex { position: [x, y, 0.0], colour: [0.0, 0.0, 0.0] }
+}
+
+// A mesh of the given number of vertices, with one triangle using the last three
+fn mesh_with(vertices: usize) -> Mesh {
+    let last = vertices as u32 - 1;
+
+    Mesh {
+        vertices: (0..vertices).map(|i| vertex(i as f32, 0.0)).collect(),
+        indices: vec![last - 2, last - 1, last],
+    }
+}
+
+#[test]
+fn union_offsets_the_appended_indices() {
+    let mut mesh = mesh_with(4);
+    let other = Mesh {
+        vertices: vec![vertex(0.0, 1.0), vertex(1.0, 1.0), vertex(0.0, 2.0)],
+        indices: vec![0, 1, 2],
+    };
+
+    mesh.union(&other);
+
+    assert_eq!(mesh.vertices.len(), 7);
+    assert_eq!(mesh.indices, vec![1, 2, 3, 4, 5, 6]);
+    assert_eq!(mesh.vertices[mesh.indices[5] as usize].position, [0.0, 2.0, 0.0]);
+}
+
+#[test]
+fn compact_indices_only_when_every_vertex_fits_a_u16() {
+    let largest = mesh_with(u16::MAX as usize + 1);
+    assert_eq!(largest.compact_indices(), Some(vec![65533, 65534, 65535]));
+
+    assert_eq!(mesh_with(u16::MAX as usize + 2).compact_indices(), None);
+}
@@ -0,0 +1,162 @@
+use marching_squares_rust_opengl::{BlendMode, Chunk, ChunkListener, Empty, MeshConfig, Meshable, NoiseGenerator, NoiseKind, Plane, ReferencePoint};
+
+#[test]
+fn coordinates_floor_towards_negative_infinity() {
+    let plane = Plane::new();
+
+    assert_eq!(plane.sample_to_chunk(0, 0), (0, 0));
+    assert_eq!(plane.sample_to_chunk(31, -1), (0, -1));
+    assert_eq!(plane.sample_to_chunk(-32, -33), (-1, -2));
+    assert_eq!(plane.sample_to_local(-1, -33), ((-1, -2), (31, 31)));
+    assert_eq!(plane.world_to_chunk(-0.5, 32.5), (-1, 1));
+    assert_eq!(plane.world_to_local(-0.5, 32.5), ((-1, 1), (31.5, 0.5)));
+    assert_eq!(plane.chunk_to_world((-2, 3)), (-64.0, 96.0));
+}
+
+#[test]
+fn circle_at_origin_is_painted_in_all_quadrants() {
+    let mut plane = Plane::new();
+    plane.paint_antialiased_filled_circle(0.0, 0.0, 6.0);
+
+    for x in -8..=8 {
+        for y in -8..=8 {
+            let value = plane.sample(x, y).unwrap();
+            assert_eq!(value, plane.sample(-x, y).unwrap(), "asymmetric at ({}, {})", x, y);
+            assert_eq!(value, plane.sample(x, -y).unwrap(), "asymmetric at ({}, {})", x, y);
+        }
+    }
+
+    assert_eq!(plane.sample(-3, -3), Some(0.0));
+    assert_eq!(plane.sample(-7, 0), Some(1.0));
+}
+
+#[test]
+fn circle_straddling_an_axis_matches_a_single_chunk() {
+    let mut plane = Plane::with_chunk_size(16);
+    plane.paint_antialiased_filled_circle(-1.5, 20.0, 5.0);
+
+    let mut reference = Plane::with_chunk_size(16);
+    reference.paint_antialiased_filled_circle(8.5, 20.0, 5.0);
+
+    for x in -8..=5 {
+        for y in 14..=26 {
+            assert_eq!(plane.sample(x, y), reference.sample(x + 10, y), "mismatch at ({}, {})", x, y);
+        }
+    }
+}
+
+#[test]
+fn generated_terrain_is_seamless_and_reproducible() {
+    let generator = NoiseGenerator::new(NoiseKind::Simplex).warp(4.0).caves(0.0, 0.25);
+    let mut plane = Plane::with_chunk_size(16).with_seed(7).with_generator(generator);
+    let mut other = Plane::with_chunk_size(8).with_seed(7).with_generator(generator);
+
+    for x in -20..20 {
+        for y in -20..20 {
+            plane.get_or_gen_chunk(plane.sample_to_chunk(x, y)).unwrap();
+            other.get_or_gen_chunk(other.sample_to_chunk(x, y)).unwrap();
+            assert_eq!(plane.sample(x, y), other.sample(x, y), "mismatch at ({}, {})", x, y);
+        }
+    }
+
+    let mut reseeded = Plane::with_chunk_size(16).with_seed(8).with_generator(generator);
+    assert!(plane.get_or_gen_chunk((0, 0)).unwrap().as_slice() != reseeded.get_or_gen_chunk((0, 0)).unwrap().as_slice());
+}
+
+#[derive(Default)]
+struct Recorder {
+    loaded: Vec<(i32, i32)>,
+    unloaded: Vec<(i32, i32)>,
+}
+
+impl ChunkListener for Recorder {
+    fn chunk_loaded(&mut self, coord: (i32, i32)) {
+        self.loaded.push(coord);
+    }
+
+    fn chunk_unloaded(&mut self, coord: (i32, i32)) {
+        self.unloaded.push(coord);
+    }
+}
+
+#[test]
+fn active_set_follows_reference_points_with_a_margin() {
+    let mut plane = Plane::with_chunk_size(8).with_unload_margin(8.0);
+    let mut recorder = Recorder::default();
+    let at = |x: f32| ReferencePoint { position: (x, 4.0), render_dist: 3.0 };
+
+    plane.update_active(&[at(4.0)], &mut recorder).unwrap();
+    assert_eq!(recorder.loaded, vec![(0, 0)]);
+
+    // Within the margin of the old position nothing is unloaded
+    plane.update_active(&[at(12.0)], &mut recorder).unwrap();
+    assert_eq!(recorder.loaded, vec![(0, 0), (1, 0)]);
+    assert!(recorder.unloaded.is_empty());
+
+    // Edited chunks have nowhere to go without storage, so they stay
+    plane.paint_antialiased_filled_circle(20.0, 4.0, 2.0);
+    plane.update_active(&[at(44.0), at(-60.0)], &mut recorder).unwrap();
+    assert_eq!(recorder.unloaded, vec![(0, 0), (1, 0)]);
+    assert!(plane.chunk((2, 0)).is_some());
+    assert!(plane.chunk((5, 0)).is_some() && plane.chunk((-8, 0)).is_some());
+}
+
+#[test]
+fn region_iteration_and_mutable_borrows_cover_loaded_chunks() {
+    let mut plane = Plane::with_chunk_size(4);
+    for coord in [(0, 0), (1, 0), (-1, 1), (1, 1), (3, 3)] {
+        plane.set_chunk(coord, Chunk::filled(4, coord.0 as f32));
+    }
+
+    // Row by row, skipping chunks that aren't loaded and those outside the region
+    let coords: Vec<(i32, i32)> = plane.chunks_in_region((-1, 0), (1, 1)).map(|(coord, _)| coord).collect();
+    assert_eq!(coords, vec![(0, 0), (1, 0), (-1, 1), (1, 1)]);
+    assert!(plane.chunks_in_region((-1, 1), (-1, 1)).all(|(_, chunk)| chunk.get(0, 0) == -1.0));
+
+    plane.remesh_dirty(&MeshConfig::default());
+    assert!(plane.chunk_mut((2, 2)).is_none());
+    assert!(!plane.is_dirty((2, 2)));
+
+    plane.chunk_mut((1, 0)).unwrap().set(2, 3, 0.5);
+    assert_eq!(plane.sample(6, 3), Some(0.5));
+    assert!(plane.is_dirty((1, 0)));
+}
+
+#[test]
+fn changing_a_chunk_remeshes_the_neighbours_that_share_its_edge() {
+    let mut plane = Plane::with_chunk_size(8);
+    for x in 0..3 {
+        for y in 0..3 {
+            plane.set_chunk((x, y), Chunk::filled(8, 1.0));
+        }
+    }
+    plane.remesh_dirty(&MeshConfig::default());
+
+    // Meshes to the left and above read chunk (1, 1)'s first row and column
+    plane.paint_circle(12.0, 12.0, 2.0, BlendMode::Carve, 0.0);
+
+    let mut remeshed = plane.remesh_dirty(&MeshConfig::default());
+    remeshed.sort();
+    assert_eq!(remeshed, vec![(0, 0), (0, 1), (1, 0), (1, 1)]);
+    assert!(plane.remesh_dirty(&MeshConfig::default()).is_empty());
+}
+
+#[test]
+fn chunks_without_loaded_neighbours_mesh_only_their_own_samples() {
+    let config = MeshConfig::default();
+
+    let mut empty = Plane::with_chunk_size(8).with_generator(Empty);
+    empty.get_or_gen_chunk((0, 0)).unwrap();
+    assert!(empty.chunk_mesh((0, 0), &config).unwrap().indices.is_empty());
+    assert!(empty.to_mesh(&config).indices.is_empty());
+    assert!(empty.contours(&config).is_empty());
+
+    // A solid chunk covers its own samples and stops there rather than padding past them
+    let mut solid = Plane::with_chunk_size(8);
+    solid.get_or_gen_chunk((1, 0)).unwrap();
+    let mesh = solid.chunk_mesh((1, 0), &config).unwrap();
+    assert!(!mesh.indices.is_empty());
+    for vertex in &mesh.vertices {
+        assert!((8.0..=15.0).contains(&vertex.position[0]) && (0.0..=7.0).contains(&vertex.position[1]), "vertex at {:?}", vertex.position);
+    }
+}
@@ -0,0 +1,123 @@
+use marching_squares_rust_opengl::{BlendMode, Chunk, Circle, Contour, CsgOp, Interpolation, MeshConfig, SdfChunk};
+
+fn linear(iso: f32) -> MeshConfig {
+    MeshConfig { iso, interpolation: Interpolation::Linear, ..Default::default() }
+}
+
+// Largest distance of any contour point from the circle
+fn circle_error(contours: &[Contour], centre: (f32, f32), radius: f32) -> f32 {
+    contours.iter()
+        .flat_map(|contour| contour.points.iter())
+        .map(|point| (((point[0] - centre.0).powi(2) + (point[1] - centre.1).powi(2)).sqrt() - radius).abs())
+        .fold(0.0, f32::max)
+}
+
+#[test]
+fn density_round_trips_through_distances() {
+    // Coverage ramps over 2 samples, so density crosses 0.5 one sample inside the radius
+    let mut chunk = Chunk::filled(32, 0.0);
+    chunk.paint(&Circle { centre: (16.0, 16.0), radius: 8.0 }, BlendMode::Fill, 2.0);
+    let config = linear(0.5);
+
+    let sdf = SdfChunk::from_density(&chunk, &config, 4.0);
+    let density = sdf.to_density(&config);
+
+    for (original, converted) in chunk.as_slice().iter().zip(density.as_slice()) {
+        assert_eq!(*original > config.iso, *converted > config.iso);
+    }
+    assert_eq!(density.contours(&config).len(), 1);
+    assert!(circle_error(&density.contours(&config), (16.0, 16.0), 7.0) < 0.1);
+    assert!(circle_error(&sdf.contours(&config), (16.0, 16.0), 7.0) < 0.1);
+}
+
+#[test]
+fn csg_ops_combine_distances() {
+    let hard = [
+        (CsgOp::Union, 1.0, -2.0, -2.0),
+        (CsgOp::Intersection, 1.0, -2.0, 1.0),
+        (CsgOp::Subtraction, -2.0, -1.0, 1.0),
+        (CsgOp::Subtraction, -2.0, 3.0, -2.0),
+    ];
+    for (op, a, b, expected) in hard {
+        assert_eq!(op.apply(a, b), expected, "{:?} of {} and {}", op, a, b);
+    }
+
+    // Smooth variants match the hard ones once the distances are the width apart,
+    // and round the seam by a quarter of the width where they meet
+    let width = 2.0;
+    let smooth = [
+        (CsgOp::SmoothUnion(width), CsgOp::Union, -0.25),
+        (CsgOp::SmoothIntersection(width), CsgOp::Intersection, 0.25),
+        (CsgOp::SmoothSubtraction(width), CsgOp::Subtraction, 0.25),
+    ];
+    for (smooth, hard, seam) in smooth {
+        // Subtraction works on the second shape's inverted distance
+        let sign: f32 = if hard == CsgOp::Subtraction { -1.0 } else { 1.0 };
+
+        for (a, b) in [(1.0f32, -2.0f32), (-3.0, 0.5), (4.0, 1.0), (-1.0, 2.0)] {
+            if (a - sign * b).abs() >= width {
+                assert_eq!(smooth.apply(a, b), hard.apply(a, b), "{:?} of {} and {}", smooth, a, b);
+            }
+        }
+
+        let b = sign;
+        assert!((smooth.apply(1.0, b) - (1.0 + seam * width)).abs() < 1e-6, "{:?} seam is {}", smooth, smooth.apply(1.0, b));
+        assert_eq!(CsgOp::SmoothUnion(0.0).apply(1.0, -2.0), CsgOp::Union.apply(1.0, -2.0));
+    }
+}
+
+#[test]
+fn painted_distances_follow_each_op() {
+    let circle = Circle { centre: (8.0, 8.0), radius: 4.0 };
+    let mut sdf = SdfChunk::new(16, 6.0);
+
+    sdf.paint(&circle, CsgOp::Union);
+    assert_eq!(sdf.get(8, 8), -4.0);
+    assert_eq!(sdf.get(14, 8), 2.0);
+
+    sdf.paint(&Circle { centre: (8.0, 8.0), radius: 2.0 }, CsgOp::Subtraction);
+    assert_eq!(sdf.get(8, 8), 2.0);
+    assert_eq!(sdf.get(11, 8), -1.0);
+
+    sdf.paint(&Circle { centre: (12.0, 8.0), radius: 2.0 }, CsgOp::Intersection);
+    assert_eq!(sdf.get(11, 8), -1.0);
+    assert_eq!(sdf.get(5, 8), 5.0);
+    // Clamped to the largest distance
+    assert_eq!(sdf.get(0, 0), 6.0);
+}
+
+#[test]
+fn contours_stay_crisp_after_repeated_edits() {
+    let config = linear(0.2);
+    let big = Circle { centre: (16.0, 16.0), radius: 9.0 };
+    let small = Circle { centre: (16.0, 16.0), radius: 4.5 };
+
+    let mut sdf = SdfChunk::new(32, 4.0);
+    for _ in 0..20 {
+        sdf.paint(&big, CsgOp::Union);
+        sdf.paint(&small, CsgOp::Subtraction);
+        sdf.paint(&small, CsgOp::Union);
+    }
+
+    let contours = sdf.contours(&config);
+    assert_eq!(contours.len(), 1);
+    assert!(circle_error(&contours, (16.0, 16.0), 9.0) < 0.05);
+}
+
+#[test]
+#[should_panic(expected = "sdf chunk size must be at least one sample")]
+fn empty_sdf_chunks_are_rejected() {
+    SdfChunk::new(0, 1.0);
+}
+
+#[test]
+#[should_panic(expected = "must be between 0 and 1")]
+fn density_needs_room_below_the_iso_value() {
+    SdfChunk::new(8, 2.0).to_density(&linear(0.0));
+}
+
+#[test]
+#[should_panic(expected = "must be between 0 and 1")]
+fn density_needs_room_above_the_iso_value() {
+    SdfChunk::new(8, 2.0).to_density(&linear(1.0));
+}
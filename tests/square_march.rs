@@ -0,0 +1,133 @@
+use std::collections::HashSet;
+
+use marching_squares_rust_opengl::{BlendMode, Chunk, Interpolation, Mesh, MeshConfig, Meshable, SaddleRule, SquareSet};
+
+// Corner bits as in SquareSet::case_index
+const A: usize = 1;
+const B: usize = 2;
+const C: usize = 4;
+const D: usize = 8;
+
+fn config(saddle: SaddleRule) -> MeshConfig {
+    MeshConfig { iso: 0.5, interpolation: Interpolation::Linear, saddle }
+}
+
+// Solid corners at 1 and empty ones at 0, so every side is cut halfway
+fn case_set(case: usize) -> SquareSet {
+    let corner = |bit: usize| if case & bit != 0 { 1.0 } else { 0.0 };
+    SquareSet { a: corner(A), b: corner(B), d: corner(D), c: corner(C) }
+}
+
+// Sum of the triangles' signed areas, positive when they all wind anticlockwise
+fn area(mesh: &Mesh) -> f32 {
+    mesh.indices.chunks_exact(3)
+        .map(|triangle| {
+            let [p, q, r] = [0, 1, 2].map(|i| mesh.vertices[triangle[i] as usize].position);
+            ((q[0] - p[0]) * (r[1] - p[1]) - (r[0] - p[0]) * (q[1] - p[1])) / 2.0
+        })
+        .sum()
+}
+
+fn has_vertex(mesh: &Mesh, position: [f32; 2]) -> bool {
+    mesh.vertices.iter().any(|v| (v.position[0] - position[0]).abs() < 1e-6 && (v.position[1] - position[1]).abs() < 1e-6)
+}
+
+#[test]
+fn every_case_fills_its_solid_corners() {
+    // Area and triangle count of each case, saddles connected
+    let expected: [(f32, usize); 16] = [
+        (0.0, 0), (0.125, 1), (0.125, 1), (0.5, 2),
+        (0.125, 1), (0.75, 4), (0.5, 2), (0.875, 3),
+        (0.125, 1), (0.5, 2), (0.75, 4), (0.875, 3),
+        (0.5, 2), (0.875, 3), (0.875, 3), (1.0, 2),
+    ];
+    let corners = [(A, [-0.5, -0.5]), (B, [0.5, -0.5]), (C, [0.5, 0.5]), (D, [-0.5, 0.5])];
+
+    for (case, (expected_area, triangles)) in expected.into_iter().enumerate() {
+        let set = case_set(case);
+        let mesh = set.to_mesh(&config(SaddleRule::Connect));
+
+        assert_eq!(set.case_index(0.5), case);
+        assert_eq!(mesh.indices.len(), triangles * 3, "case {}", case);
+        assert!((area(&mesh) - expected_area).abs() < 1e-6, "case {} has area {}", case, area(&mesh));
+
+        for (bit, position) in corners {
+            assert_eq!(has_vertex(&mesh, position), case & bit != 0, "case {} corner {}", case, bit);
+        }
+    }
+}
+
+#[test]
+fn saddle_rules_connect_or_separate_the_diagonal() {
+    // Case 5 with a and c solid, and the same values turned into case 10 with b and d solid
+    let saddles = |first: f32, second: f32, low: f32| [
+        SquareSet { a: first, b: low, d: low, c: second },
+        SquareSet { a: low, b: first, d: second, c: low },
+    ];
+
+    // Values with their expected connection under Connect, Separate, CenterAverage and AsymptoticDecider
+    let cases = [
+        // Well above the cutoff in the middle, joined by every rule but Separate
+        (saddles(1.0, 1.0, 0.4), [true, false, true, true]),
+        // Barely solid corners, split by both deciders
+        (saddles(0.6, 0.6, 0.0), [true, false, false, false]),
+        // The average is above the cutoff but the bilinear saddle point is below it
+        (saddles(1.0, 0.51, 0.3), [true, false, true, false]),
+    ];
+    let rules = [SaddleRule::Connect, SaddleRule::Separate, SaddleRule::CenterAverage, SaddleRule::AsymptoticDecider];
+
+    for (sets, connected) in cases {
+        for set in sets {
+            for (rule, connects) in rules.into_iter().zip(connected) {
+                let mesh = set.to_mesh(&config(rule));
+
+                // A connected saddle is one hexagon, a separated one two corner triangles
+                assert_eq!(mesh.indices.len(), if connects { 12 } else { 6 }, "{:?} on case {}", rule, set.case_index(0.5));
+                assert!(area(&mesh) > 0.0);
+            }
+        }
+    }
+}
+
+#[test]
+fn asymptotic_decider_handles_nearly_flat_saddles() {
+    // Corners just either side of the cutoff give a tiny denominator, which stays
+    // non-zero and decides like the average
+    let above = 0.5 + 1.0 / 1024.0;
+    let below = 0.5;
+
+    let flat = [
+        SquareSet { a: above, b: below, d: below, c: above },
+        SquareSet { a: below, b: above, d: above, c: below },
+    ];
+
+    for set in flat {
+        for rule in [SaddleRule::CenterAverage, SaddleRule::AsymptoticDecider] {
+            let mesh = set.to_mesh(&config(rule));
+
+            assert_eq!(mesh.indices.len(), 12, "{:?} on case {}", rule, set.case_index(0.5));
+            assert!(mesh.vertices.iter().all(|v| v.position.iter().all(|p| p.is_finite())));
+        }
+    }
+}
+
+#[test]
+fn neighbouring_sets_share_their_vertices() {
+    let config = config(SaddleRule::Connect);
+
+    // Every sample of a solid chunk is one corner vertex, shared by up to four sets
+    for size in [2, 3, 16] {
+        let mesh = SquareSet::chunk_to_sets(&Chunk::filled(size, 1.0)).to_mesh(&config);
+        assert_eq!(mesh.vertices.len(), size * size);
+        assert_eq!(mesh.indices.len(), (size - 1) * (size - 1) * 6);
+    }
+
+    // A circle's edge crosses many sets, each side it cuts giving one vertex for both
+    let mut chunk = Chunk::filled(16, 0.0);
+    chunk.paint_circle(7.5, 8.0, 5.0, BlendMode::Fill, 3.0);
+    let mesh = SquareSet::chunk_to_sets(&chunk).to_mesh(&config);
+
+    let positions: HashSet<[u32; 3]> = mesh.vertices.iter().map(|v| v.position.map(f32::to_bits)).collect();
+    assert!(mesh.vertices.len() > 40);
+    assert_eq!(positions.len(), mesh.vertices.len(), "vertices at the same position weren't welded");
+}
@@ -0,0 +1,307 @@
+use std::path::PathBuf;
+
+use image::DynamicImage;
+use marching_squares_rust_opengl::{Chunk, ExportOptions, ImageOptions, MeshConfig, Plane, ReferencePoint, RegionStore, WorldError};
+
+fn temp_directory(name: &str) -> PathBuf {
+    let directory = std::env::temp_dir().join(format!("marching-squares-{}-{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&directory);
+
+    directory
+}
+
+fn painted_plane() -> Plane {
+    let mut plane = Plane::with_chunk_size(16).with_seed(-12);
+    plane.paint_antialiased_filled_circle(3.5, -2.25, 9.0);
+    plane.get_or_gen_chunk((4, 4)).unwrap();
+
+    plane
+}
+
+#[test]
+fn saved_plane_loads_back() {
+    let plane = painted_plane();
+    let mut bytes = vec![];
+    plane.write_to(&mut bytes).unwrap();
+
+    let loaded = Plane::read_from(&mut bytes.as_slice()).unwrap();
+    assert_eq!(loaded.seed(), -12);
+    assert_eq!(loaded.chunk_size(), 16);
+    assert_eq!(loaded.total_chunks(), plane.total_chunks());
+
+    for (coord, chunk) in plane.chunks() {
+        let loaded_chunk = loaded.chunk(coord).unwrap();
+        for (saved, loaded) in chunk.as_slice().iter().zip(loaded_chunk.as_slice()) {
+            assert!((saved - loaded).abs() < 1e-4, "{} became {} in chunk {:?}", saved, loaded, coord);
+            // Empty and solid samples are exact
+            if *saved == 0.0 || *saved == 1.0 {
+                assert_eq!(saved, loaded);
+            }
+        }
+    }
+}
+
+#[test]
+fn bad_files_give_clear_errors() {
+    let mut bytes = vec![];
+    painted_plane().write_to(&mut bytes).unwrap();
+
+    let mut wrong_magic = bytes.clone();
+    wrong_magic[0] = b'X';
+    assert!(matches!(Plane::read_from(&mut wrong_magic.as_slice()), Err(WorldError::BadMagic(_))));
+
+    for version in [0, 2, 99] {
+        let mut other_version = bytes.clone();
+        other_version[4] = version;
+        assert!(matches!(Plane::read_from(&mut other_version.as_slice()), Err(WorldError::UnsupportedVersion(v)) if v == version as u32));
+    }
+
+    // The first chunk is quantised, its range follows the 20 byte header, coordinate and encoding
+    assert_eq!(bytes[28], 1);
+    for (min, max) in [(f32::NAN, 1.0), (0.0, f32::INFINITY), (f32::NEG_INFINITY, 1.0), (0.75, 0.25)] {
+        let mut bad_range = bytes.clone();
+        bad_range[29..33].copy_from_slice(&min.to_le_bytes());
+        bad_range[33..37].copy_from_slice(&max.to_le_bytes());
+        assert!(matches!(Plane::read_from(&mut bad_range.as_slice()), Err(WorldError::Corrupt(_))), "range {} to {} was accepted", min, max);
+    }
+
+    // The last chunk is uniform, its value ends the file
+    let end = bytes.len();
+    assert_eq!(bytes[end - 5], 0);
+    let mut bad_value = bytes.clone();
+    bad_value[end - 4..].copy_from_slice(&f32::NAN.to_le_bytes());
+    assert!(matches!(Plane::read_from(&mut bad_value.as_slice()), Err(WorldError::Corrupt(_))));
+
+    let truncated = &bytes[..bytes.len() - 7];
+    assert!(matches!(Plane::read_from(&mut &truncated[..]), Err(WorldError::Truncated)));
+}
+
+#[test]
+fn chunks_paged_out_over_budget_stream_back_from_regions() {
+    let directory = temp_directory("regions");
+
+    let storage = RegionStore::with_region_size(&directory, 0, 8, 4).unwrap();
+    let mut plane = Plane::with_chunk_size(8).with_storage(storage).with_memory_budget(4);
+    plane.paint_antialiased_filled_circle(-2.0, 3.0, 4.0);
+    let painted = plane.get_chunk((-1, 0)).unwrap();
+
+    // Exploring far away pushes the painted chunks out of memory and into region files
+    plane.stream(&ReferencePoint { position: (100.0, -60.0), render_dist: 4.0 }).unwrap();
+    assert!(plane.chunk((-1, 0)).is_none());
+    assert!(plane.total_chunks() <= 4);
+
+    plane.stream(&ReferencePoint { position: (-4.0, 4.0), render_dist: 1.0 }).unwrap();
+    let streamed = plane.chunk((-1, 0)).unwrap();
+    for (painted, streamed) in painted.as_slice().iter().zip(streamed.as_slice()) {
+        assert!((painted - streamed).abs() < 1e-4, "{} became {}", painted, streamed);
+    }
+
+    std::fs::remove_dir_all(&directory).unwrap();
+}
+
+#[test]
+fn reading_and_importing_chunks_leaves_them_unchanged() {
+    let directory = temp_directory("reads");
+
+    let storage = RegionStore::with_region_size(&directory, 0, 8, 4).unwrap();
+    let image = DynamicImage::new_luma8(12, 12);
+    let mut plane = Plane::from_image(&image, 8, ImageOptions::default()).with_storage(storage.clone());
+    plane.get_or_gen_chunk((5, 5)).unwrap();
+
+    plane.remesh_dirty(&MeshConfig::default());
+    plane.get_or_gen_chunk((0, 0)).unwrap();
+    assert!(!plane.is_dirty((0, 0)));
+
+    // Only chunks that were written to are stored
+    plane.chunk_or_generate_mut((1, 1)).unwrap();
+    plane.flush().unwrap();
+    for coord in [(0, 0), (1, 0), (5, 5)] {
+        assert!(storage.load_chunk(coord).unwrap().is_none(), "chunk {:?} was stored", coord);
+    }
+    assert!(storage.load_chunk((1, 1)).unwrap().is_some());
+
+    std::fs::remove_dir_all(&directory).unwrap();
+}
+
+#[test]
+fn region_export_reads_paged_out_chunks_from_storage() {
+    let directory = temp_directory("export");
+
+    let storage = RegionStore::with_region_size(&directory, 0, 8, 4).unwrap();
+    let mut plane = Plane::with_chunk_size(8).with_storage(storage);
+    plane.paint_antialiased_filled_circle(4.0, 4.0, 2.0);
+    plane.page_out((0, 0)).unwrap();
+    assert!(plane.chunk((0, 0)).is_none());
+
+    let options = ExportOptions::default();
+    let image = plane.region_to_image((0, 0), (15, 7), &options);
+
+    // The carved circle comes back from the region file, chunk (1, 0) was never stored
+    assert_eq!(image.get_pixel(4, 4).0, [0, 0, 0]);
+    assert_eq!(image.get_pixel(0, 0).0, [255, 255, 255]);
+    assert_eq!(image.get_pixel(12, 4).0, options.missing);
+
+    std::fs::remove_dir_all(&directory).unwrap();
+}
+
+#[test]
+fn unreadable_stored_chunks_are_never_overwritten() {
+    let directory = temp_directory("corrupt");
+
+    let storage = RegionStore::with_region_size(&directory, 0, 8, 4).unwrap();
+    let mut plane = Plane::with_chunk_size(8).with_storage(storage);
+    plane.paint_antialiased_filled_circle(4.0, 4.0, 2.0);
+    plane.page_out((0, 0)).unwrap();
+
+    // Give chunk (0, 0) an unknown encoding. Its table entry is the first after the
+    // 16 byte header, and the encoding follows the stored coordinate
+    let path = directory.join("r.0.0.msqr");
+    let mut bytes = std::fs::read(&path).unwrap();
+    let offset = u64::from_le_bytes(bytes[16..24].try_into().unwrap()) as usize;
+    bytes[offset + 8] = 7;
+    std::fs::write(&path, &bytes).unwrap();
+
+    assert!(matches!(plane.get_or_gen_chunk((0, 0)), Err(WorldError::Corrupt(_))));
+    assert!(matches!(plane.chunk_or_generate_mut((0, 0)), Err(WorldError::Corrupt(_))));
+
+    // Painting over it and saving leaves the stored bytes alone
+    plane.paint_antialiased_filled_circle(4.0, 4.0, 3.0);
+    assert!(plane.chunk((0, 0)).is_none());
+    plane.flush().unwrap();
+    assert_eq!(std::fs::read(&path).unwrap(), bytes);
+
+    std::fs::remove_dir_all(&directory).unwrap();
+}
+
+#[test]
+fn saving_includes_chunks_paged_out_to_regions() {
+    let directory = temp_directory("save");
+
+    let storage = RegionStore::with_region_size(&directory, 3, 8, 4).unwrap();
+    let mut plane = Plane::with_chunk_size(8).with_seed(3).with_storage(storage).with_memory_budget(2);
+    plane.paint_antialiased_filled_circle(-2.0, 3.0, 4.0);
+    plane.paint_antialiased_filled_circle(100.0, 60.0, 2.0);
+    let painted = plane.get_chunk((-1, 0)).unwrap();
+
+    plane.stream(&ReferencePoint { position: (300.0, 300.0), render_dist: 1.0 }).unwrap();
+    assert!(plane.chunk((-1, 0)).is_none());
+
+    let mut bytes = vec![];
+    plane.write_to(&mut bytes).unwrap();
+    let loaded = Plane::read_from(&mut bytes.as_slice()).unwrap();
+
+    // Both circles' chunks come back along with the chunk streamed in at the end
+    assert_eq!(loaded.total_chunks(), 2 + 1 + 1);
+    assert!(loaded.chunk((12, 7)).is_some() && loaded.chunk((37, 37)).is_some());
+    for (painted, saved) in painted.as_slice().iter().zip(loaded.chunk((-1, 0)).unwrap().as_slice()) {
+        assert!((painted - saved).abs() < 1e-4, "{} became {}", painted, saved);
+    }
+
+    std::fs::remove_dir_all(&directory).unwrap();
+}
+
+#[test]
+fn regions_reclaim_space_left_by_moved_chunks() {
+    let directory = temp_directory("compact");
+    let storage = RegionStore::with_region_size(&directory, 0, 8, 4).unwrap();
+
+    let other = Chunk::filled(8, 0.75);
+    storage.save_chunk((1, 0), &other).unwrap();
+
+    // Alternating between a uniform chunk and one that doesn't fit in its space moves the
+    // chunk to the end of the file every other save
+    let uniform = Chunk::filled(8, 0.25);
+    let mut quantised = Chunk::filled(8, 0.25);
+    quantised.set(3, 5, 1.0);
+    for _ in 0..100 {
+        storage.save_chunk((0, 0), &uniform).unwrap();
+        storage.save_chunk((0, 0), &quantised).unwrap();
+    }
+
+    // A 16 byte header, 16 table entries of 12 bytes and a few quantised chunks at most
+    let length = std::fs::metadata(directory.join("r.0.0.msqr")).unwrap().len();
+    assert!(length < 16 + 16 * 12 + 4 * 145, "region grew to {} bytes", length);
+
+    assert_eq!(storage.load_chunk((0, 0)).unwrap().unwrap().as_slice(), quantised.as_slice());
+    assert_eq!(storage.load_chunk((1, 0)).unwrap().unwrap().as_slice(), other.as_slice());
+    let mut stored = storage.stored_chunks().unwrap();
+    stored.sort();
+    assert_eq!(stored, vec![(0, 0), (1, 0)]);
+
+    std::fs::remove_dir_all(&directory).unwrap();
+}
+
+#[test]
+fn reopened_regions_keep_their_seed_and_chunk_size() {
+    let directory = temp_directory("reopen");
+    assert!(matches!(RegionStore::open(&directory), Err(WorldError::Io(error)) if error.kind() == std::io::ErrorKind::NotFound));
+
+    let storage = RegionStore::new(&directory, -7, 8).unwrap();
+    let mut plane = Plane::with_chunk_size(8).with_seed(-7).with_storage(storage);
+    plane.paint_antialiased_filled_circle(4.0, 4.0, 2.0);
+    let painted = plane.get_chunk((0, 0)).unwrap();
+    plane.flush().unwrap();
+
+    let mut reopened = Plane::from_storage(RegionStore::open(&directory).unwrap());
+    assert_eq!((reopened.seed(), reopened.chunk_size()), (-7, 8));
+    for (painted, stored) in painted.as_slice().iter().zip(reopened.get_or_gen_chunk((0, 0)).unwrap().as_slice()) {
+        assert!((painted - stored).abs() < 1e-4, "{} became {}", painted, stored);
+    }
+
+    // The same directory can't be used for another world
+    assert!(matches!(RegionStore::new(&directory, 5, 8), Err(WorldError::Corrupt(_))));
+    assert!(matches!(RegionStore::new(&directory, -7, 16), Err(WorldError::Corrupt(_))));
+    assert!(RegionStore::new(&directory, -7, 8).is_ok());
+
+    std::fs::remove_dir_all(&directory).unwrap();
+}
+
+#[test]
+fn reopened_regions_keep_their_region_size() {
+    let directory = temp_directory("reopen-region-size");
+
+    let storage = RegionStore::with_region_size(&directory, 3, 8, 4).unwrap();
+    let mut plane = Plane::with_chunk_size(8).with_seed(3).with_storage(storage);
+    plane.paint_antialiased_filled_circle(-20.0, 44.0, 3.0);
+    let painted = plane.get_chunk((-3, 5)).unwrap();
+    plane.flush().unwrap();
+
+    let storage = RegionStore::open(&directory).unwrap();
+    assert_eq!((storage.seed(), storage.chunk_size(), storage.region_size()), (3, 8, 4));
+    assert!(directory.join("r.-1.1.msqr").exists());
+
+    let mut reopened = Plane::from_storage(storage);
+    for (painted, stored) in painted.as_slice().iter().zip(reopened.get_or_gen_chunk((-3, 5)).unwrap().as_slice()) {
+        assert!((painted - stored).abs() < 1e-4, "{} became {}", painted, stored);
+    }
+
+    assert!(matches!(RegionStore::new(&directory, 3, 8), Err(WorldError::Corrupt(_))));
+    assert!(RegionStore::with_region_size(&directory, 3, 8, 4).is_ok());
+
+    std::fs::remove_dir_all(&directory).unwrap();
+}
+
+#[test]
+#[should_panic(expected = "region store seed does not match the plane")]
+fn seeds_set_after_storage_must_match_it() {
+    let storage = RegionStore::with_region_size(temp_directory("late-seed"), 3, 8, 4).unwrap();
+    let _ = Plane::with_chunk_size(8).with_seed(3).with_storage(storage).with_seed(5);
+}
+
+#[test]
+fn memory_budget_applies_when_streaming() {
+    let directory = temp_directory("budget");
+
+    let storage = RegionStore::with_region_size(&directory, 0, 8, 4).unwrap();
+    let mut plane = Plane::with_chunk_size(8).with_storage(storage).with_memory_budget(2);
+    for x in 0..10 {
+        plane.paint_antialiased_filled_circle(x as f32 * 8.0 + 4.0, 4.0, 2.0);
+    }
+    assert_eq!(plane.total_chunks(), 10);
+
+    plane.stream(&ReferencePoint { position: (300.0, 300.0), render_dist: 1.0 }).unwrap();
+    assert!(plane.total_chunks() <= 2 + 4, "{} chunks loaded", plane.total_chunks());
+    assert_eq!(plane.get_or_gen_chunk((0, 0)).unwrap().get(4, 4), 0.0);
+
+    std::fs::remove_dir_all(&directory).unwrap();
+}